@@ -0,0 +1,73 @@
+use crate::*;
+
+/*  Body surface area and lean body mass.
+    Clinical users dose and assess on BSA/LBM rather than BMI, so these views derive them from the
+    weight (kg) and height (cm) stored with the user's record. LBM formulas also need the sex from
+    the user's profile.
+*/
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "lowercase")]
+pub enum Sex {
+  Male,
+  Female,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum BsaMethod {
+  DuBois,
+  Mosteller,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum LbmMethod {
+  Boer,
+  Hume,
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn set_sex(&mut self, sex: Sex) {
+    self.track_call("set_sex");
+    self.assert_writable();
+    // Only the user themselves, never a contract they call, may change their profile
+    let account = env::predecessor_account_id().to_string();
+    match self.app_user.get_mut(&account) {
+      Some(user) => user.sex = Some(sex),
+      None => env::panic_str("Set a user before updating the profile"),
+    }
+    self.emit_event_for(&account, "profile_updated", json!({ "sex": sex }));
+  }
+
+  // Body surface area in square meters
+  pub fn compute_bsa(&self, account: AccountId, method: BsaMethod) -> Option<f32> {
+    let data = self.data.get(&account)?;
+    let (weight, height) = (data.weight as f32, data.height);
+    let bsa = match method {
+      BsaMethod::DuBois => 0.007184 * weight.powf(0.425) * height.powf(0.725),
+      BsaMethod::Mosteller => (weight * height / 3600.0).sqrt(),
+    };
+    Some(bsa)
+  }
+
+  // Lean body mass in kilograms
+  pub fn compute_lbm(&self, account: AccountId, method: LbmMethod) -> Option<f32> {
+    let data = self.data.get(&account)?;
+    let sex = self.app_user.get(&account)?.sex?;
+    let (weight, height) = (data.weight as f32, data.height);
+    let lbm = match (method, sex) {
+      (LbmMethod::Boer, Sex::Male) => 0.407 * weight + 0.267 * height - 19.2,
+      (LbmMethod::Boer, Sex::Female) => 0.252 * weight + 0.473 * height - 48.3,
+      (LbmMethod::Hume, Sex::Male) => 0.32810 * weight + 0.33929 * height - 29.5336,
+      (LbmMethod::Hume, Sex::Female) => 0.29569 * weight + 0.41813 * height - 43.2933,
+    };
+    Some(lbm)
+  }
+}
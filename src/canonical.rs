@@ -0,0 +1,152 @@
+use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+
+/*  Canonical encoding and hashing.
+    Attestations, commitments and digests all hash records through this one code path. The encoding
+    is independent of the storage layout: a version byte, then fields in a fixed order, integers
+    little-endian, strings length-prefixed, enums as explicit tags and floats as fixed-point
+    (value * 10_000, rounded), so the same record always produces the same bytes.
+*/
+
+const CANONICAL_VERSION: u8 = 1;
+const FIXED_POINT: f64 = 10_000.0;
+
+pub trait Canonical {
+  fn write_canonical(&self, writer: &mut CanonicalWriter);
+
+  fn canonical_bytes(&self) -> Vec<u8> {
+    let mut writer = CanonicalWriter::new();
+    self.write_canonical(&mut writer);
+    writer.bytes
+  }
+
+  fn canonical_hash(&self) -> Base58CryptoHash {
+    env::sha256_array(&self.canonical_bytes()).into()
+  }
+}
+
+pub struct CanonicalWriter {
+  bytes: Vec<u8>,
+}
+
+impl CanonicalWriter {
+  pub fn new() -> Self {
+    Self {
+      bytes: vec![CANONICAL_VERSION],
+    }
+  }
+
+  pub fn u8(&mut self, value: u8) -> &mut Self {
+    self.bytes.push(value);
+    self
+  }
+
+  pub fn u64(&mut self, value: u64) -> &mut Self {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+    self
+  }
+
+  pub fn fixed(&mut self, value: f32) -> &mut Self {
+    let fixed = (value as f64 * FIXED_POINT).round() as i64;
+    self.bytes.extend_from_slice(&fixed.to_le_bytes());
+    self
+  }
+
+  pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+    self
+      .bytes
+      .extend_from_slice(&(value.len() as u32).to_le_bytes());
+    self.bytes.extend_from_slice(value);
+    self
+  }
+
+  pub fn str(&mut self, value: &str) -> &mut Self {
+    self.bytes(value.as_bytes())
+  }
+
+  pub fn opt_u64(&mut self, value: Option<u64>) -> &mut Self {
+    match value {
+      Some(value) => self.u8(1).u64(value),
+      None => self.u8(0),
+    }
+  }
+
+  pub fn finish(&self) -> Base58CryptoHash {
+    env::sha256_array(&self.bytes).into()
+  }
+}
+
+impl Default for CanonicalWriter {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Canonical for Category {
+  fn write_canonical(&self, writer: &mut CanonicalWriter) {
+    writer.u8(match self {
+      Category::Underweight => 0,
+      Category::Normal => 1,
+      Category::Overweight => 2,
+      Category::Obese => 3,
+    });
+  }
+}
+
+impl Canonical for Formula {
+  fn write_canonical(&self, writer: &mut CanonicalWriter) {
+    match self {
+      Formula::Standard => writer.u8(0),
+      Formula::Trefethen => writer.u8(1),
+      Formula::Custom {
+        coefficient,
+        exponent,
+      } => writer.u8(2).fixed(*coefficient).fixed(*exponent),
+    };
+  }
+}
+
+impl Canonical for Data {
+  fn write_canonical(&self, writer: &mut CanonicalWriter) {
+    writer
+      .str(&self.uid)
+      .u64(self.weight as u64)
+      .fixed(self.height)
+      .fixed(self.bmi)
+      .u8(match self.source {
+        Source::Manual => 0,
+        Source::Device => 1,
+        Source::Clinician => 2,
+        Source::Oracle => 3,
+      })
+      .opt_u64(self.confidence.map(u64::from))
+      .opt_u64(self.receipt);
+    self.formula.write_canonical(writer);
+  }
+}
+
+impl Canonical for AppUser {
+  fn write_canonical(&self, writer: &mut CanonicalWriter) {
+    writer.u64(self.id as u64).str(&self.uid);
+    match &self.u_name {
+      Some(u_name) => writer.u8(1).str(u_name),
+      None => writer.u8(0),
+    };
+    writer.u8(match self.sex {
+      None => 0,
+      Some(Sex::Male) => 1,
+      Some(Sex::Female) => 2,
+    });
+  }
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn hash_record(&self, account: AccountId) -> Option<Base58CryptoHash> {
+    self.data.get(&account).map(Canonical::canonical_hash)
+  }
+
+  pub fn hash_profile(&self, account: AccountId) -> Option<Base58CryptoHash> {
+    self.app_user.get(&account).map(Canonical::canonical_hash)
+  }
+}
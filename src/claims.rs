@@ -0,0 +1,58 @@
+use crate::*;
+
+/*  Third-party claim verification.
+    A verifier the user granted `verify:claims` (e.g. an insurer contract) asks whether a claim about
+    them holds. The answer is only a boolean plus a reference to the consent receipt the underlying
+    record was stored under, so the verifier needs no more than that. This is a convenience, not a
+    confidentiality guarantee: the raw record is public through `get_data`, `project`, `compute_bsa`,
+    `compute_lbm`, `export_state_page` and the contract state.
+*/
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Claim {
+  // A BMI record is stored
+  HasRecord,
+  // The stored BMI falls in this category
+  InCategory(Category),
+  // The record was stored under consent that has not expired
+  ConsentCurrent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClaimResult {
+  pub holds: bool,
+  pub proof: Option<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn verify_claim(&mut self, account: AccountId, claim: Claim) -> ClaimResult {
+    self.track_call("verify_claim");
+    self.assert_scope(&account, Scope::VerifyClaims);
+    self.audit_scoped_access(&account, Scope::VerifyClaims);
+    let data = match self.data.get(&account) {
+      Some(data) => data,
+      None => {
+        return ClaimResult {
+          holds: false,
+          proof: None,
+        }
+      }
+    };
+    let holds = match claim {
+      Claim::HasRecord => true,
+      Claim::InCategory(category) => Category::of(data.bmi) == category,
+      Claim::ConsentCurrent => data
+        .receipt
+        .and_then(|id| self.consent_receipt(account.clone(), id))
+        .is_some_and(|receipt| !receipt.lapsed && receipt.expires_at > env::block_timestamp()),
+    };
+    ClaimResult {
+      holds,
+      proof: data.receipt,
+    }
+  }
+}
@@ -0,0 +1,51 @@
+use crate::*;
+use near_sdk::assert_one_yocto;
+use near_sdk::json_types::Base58CryptoHash;
+
+/*  Account closure.
+    `close_account` hands back everything the contract holds about the caller in the transaction
+    result and deletes it in the same call, so offboarding is a single atomic step. Only the
+    per-account event sequence number is kept, so indexers can still order the closure event.
+    The contract takes no deposits and sells no credits, so there is nothing to refund.
+    Closing acts for the predecessor and needs exactly one yoctoNEAR attached, which only a full access
+    key can sign, so no intermediate contract or function-call key can wipe an account. `delete_data`
+    is held to the same rule for the caller's own record.
+*/
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountExport {
+  pub profile: Option<AppUser>,
+  pub record: Option<Data>,
+  pub grants: HashMap<AccountId, Grant>,
+  pub receipts: Vec<ConsentReceipt>,
+  pub band_proofs: Vec<Base58CryptoHash>,
+  pub excluded_processors: Vec<AccountId>,
+}
+
+#[near_bindgen]
+impl Contract {
+  #[payable]
+  pub fn close_account(&mut self) -> AccountExport {
+    assert_one_yocto();
+    self.track_call("close_account");
+    self.assert_writable();
+    let account = env::predecessor_account_id().to_string();
+    let export = AccountExport {
+      profile: self.app_user.remove(&account),
+      record: self.data.remove(&account),
+      grants: self.grants.remove(&account).unwrap_or_default(),
+      receipts: self.receipts.remove(&account).unwrap_or_default(),
+      band_proofs: self.band_proofs.remove(&account).unwrap_or_default(),
+      excluded_processors: self
+        .excluded_processors
+        .remove(&account)
+        .unwrap_or_default(),
+    };
+    if let Some(record) = &export.record {
+      self.population_remove(record);
+    }
+    self.emit_event_for(&account, "account_closed", json!({}));
+    export
+  }
+}
@@ -0,0 +1,54 @@
+use crate::*;
+
+/*  Per-deployment branding and configuration.
+    White-label frontends read this object from the chain to configure themselves,
+    so every field has a sensible default for a freshly deployed contract.
+    Feature toggles are not part of it; they live in the runtime feature flags.
+*/
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+  #[default]
+  Metric,
+  Imperial,
+}
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeploymentConfig {
+  pub app_name: String,
+  pub support_contact: Option<String>,
+  pub default_language: String,
+  pub default_units: Units,
+}
+
+impl Default for DeploymentConfig {
+  fn default() -> Self {
+    Self {
+      app_name: "Smart BMI".to_string(),
+      support_contact: None,
+      default_language: "en".to_string(),
+      default_units: Units::default(),
+    }
+  }
+}
+
+#[near_bindgen]
+impl Contract {
+  // Owner replaces the whole deployment config in one call
+  pub fn set_config(&mut self, config: DeploymentConfig) {
+    self.track_call("set_config");
+    self.assert_owner("change the config");
+    require!(!config.app_name.is_empty(), "App name can not be empty");
+    self.config = config;
+    self.emit_event("config_updated", json!({}));
+  }
+
+  pub fn get_config(&self) -> DeploymentConfig {
+    self.config.clone()
+  }
+}
@@ -0,0 +1,128 @@
+use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+
+/*  Consent receipts.
+    Whenever a user grants consent, a receipt records which policy version (by hash) governed it,
+    what was consented to and for how long. Records stored under that consent keep the receipt id,
+    so we can always prove which policy applied to which data; scoped writes refer to the receipt of
+    the grant they were made under. A receipt ends when its grant is revoked or replaced, or when it
+    expires. It is then removed, unless the stored record refers to it, in which case it is kept and
+    marked lapsed.
+*/
+
+pub const DEFAULT_CONSENT_VALIDITY_NS: u64 = 1_000_000_000 * 60 * 60 * 24 * 365;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConsentReceipt {
+  pub id: u64,
+  pub policy_hash: Base58CryptoHash,
+  pub scopes: Vec<String>,
+  pub granted_at: u64,
+  pub expires_at: u64,
+  pub lapsed: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+  // Owner publishes a new policy version; receipts minted from now on reference it
+  pub fn set_consent_policy(&mut self, policy_hash: Base58CryptoHash, validity_ns: u64) {
+    self.track_call("set_consent_policy");
+    self.assert_owner("change the consent policy");
+    require!(validity_ns > 0, "Consent validity must be positive");
+    self.consent_policy = policy_hash;
+    self.consent_validity_ns = validity_ns;
+    self.emit_event(
+      "consent_policy_updated",
+      json!({ "policy_hash": policy_hash, "validity_ns": validity_ns }),
+    );
+  }
+
+  pub fn get_consent_policy(&self) -> (Base58CryptoHash, u64) {
+    (self.consent_policy, self.consent_validity_ns)
+  }
+
+  pub fn consent_receipts_of(&self, account: AccountId) -> Vec<ConsentReceipt> {
+    self.receipts.get(&account).cloned().unwrap_or_default()
+  }
+
+  pub fn consent_receipt(&self, account: AccountId, id: u64) -> Option<ConsentReceipt> {
+    self
+      .receipts
+      .get(&account)
+      .and_then(|receipts| receipts.iter().find(|receipt| receipt.id == id))
+      .cloned()
+  }
+}
+
+impl Contract {
+  // Record that `account` consented to `scopes` under the current policy
+  pub(crate) fn mint_receipt(&mut self, account: &AccountId, scopes: Vec<String>) -> u64 {
+    let id = self.next_receipt_id;
+    self.next_receipt_id += 1;
+    let granted_at = env::block_timestamp();
+    let receipt = ConsentReceipt {
+      id,
+      policy_hash: self.consent_policy,
+      scopes,
+      granted_at,
+      expires_at: granted_at.saturating_add(self.consent_validity_ns),
+      lapsed: false,
+    };
+    self.emit_event_for(
+      account,
+      "consent_receipt",
+      json!({ "owner": account, "receipt": receipt }),
+    );
+    self
+      .receipts
+      .entry(account.clone())
+      .or_default()
+      .push(receipt);
+    id
+  }
+  // Whether the stored record of `account` was written under receipt `id`
+  pub(crate) fn receipt_in_use(&self, account: &AccountId, id: u64) -> bool {
+    self
+      .data
+      .get(account)
+      .is_some_and(|data| data.receipt == Some(id))
+  }
+
+  // End receipt `id` of `account`: the receipt of the stored record is kept, marked lapsed, as proof
+  // of the consent the record was written under; any other is removed
+  pub(crate) fn retire_receipt(&mut self, account: &AccountId, id: u64) {
+    if !self.receipt_in_use(account, id) {
+      self.drop_receipt(account, id);
+      return;
+    }
+    if let Some(receipt) = self
+      .receipts
+      .get_mut(account)
+      .and_then(|receipts| receipts.iter_mut().find(|receipt| receipt.id == id))
+      .filter(|receipt| !receipt.lapsed)
+    {
+      receipt.lapsed = true;
+      self.emit_event_for(
+        account,
+        "consent_lapsed",
+        json!({ "owner": account, "receipt": id }),
+      );
+    }
+  }
+
+  // Remove receipt `id` of `account`, e.g. once a newer one supersedes it
+  pub(crate) fn drop_receipt(&mut self, account: &AccountId, id: u64) {
+    if let Some(receipts) = self.receipts.get_mut(account) {
+      receipts.retain(|receipt| receipt.id != id);
+      if receipts.is_empty() {
+        self.receipts.remove(account);
+      }
+    }
+    self.emit_event_for(
+      account,
+      "consent_removed",
+      json!({ "owner": account, "receipt": id }),
+    );
+  }
+}
@@ -0,0 +1,94 @@
+use crate::*;
+
+/*  Public demo mode.
+    For workshops the owner enables demo mode with a shared daily quota. Anyone can then `preview`
+    a BMI without storing anything, and accounts that were not registered users when the owner enabled
+    demo mode can store computations until the day's shared quota runs out. After that their results
+    are still computed, just not stored. Registering during the demo does not lift the quota.
+*/
+
+const NANOS_PER_DAY: u64 = 1_000_000_000 * 60 * 60 * 24;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DemoMode {
+  pub daily_quota: u32,
+  pub day: u64,
+  pub used: u32,
+  // Users with a lower id were registered before demo mode and store freely
+  pub exempt_below: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Preview {
+  pub bmi: f32,
+  pub category: Category,
+}
+
+#[near_bindgen]
+impl Contract {
+  // Enable demo mode with a shared daily storage quota, or disable it with null
+  pub fn set_demo_mode(&mut self, daily_quota: Option<u32>) {
+    self.track_call("set_demo_mode");
+    self.assert_owner("change the demo mode");
+    self.demo = daily_quota.map(|daily_quota| DemoMode {
+      daily_quota,
+      day: env::block_timestamp() / NANOS_PER_DAY,
+      used: 0,
+      exempt_below: self.next_user_id,
+    });
+    self.emit_event("demo_mode_updated", json!({ "daily_quota": daily_quota }));
+  }
+
+  pub fn demo_status(&self) -> Option<DemoMode> {
+    self.demo.clone()
+  }
+
+  // BMI with the live formula, validated like a stored record but stored nowhere
+  pub fn preview(&self, weight: u32, height: f32) -> Preview {
+    require!(self.demo.is_some(), "Demo mode is disabled");
+    let bmi = self.formula.bmi(weight as f32, height / 100.0);
+    registry::assert_valid_record(&Data {
+      uid: env::predecessor_account_id().to_string(),
+      weight,
+      height,
+      bmi,
+      source: Source::Manual,
+      confidence: None,
+      receipt: None,
+      formula: self.formula,
+    });
+    Preview {
+      bmi,
+      category: Category::of(bmi),
+    }
+  }
+}
+
+impl Contract {
+  // Whether `account` may store another record; accounts registered since demo mode draw from the quota
+  pub(crate) fn take_demo_quota(&mut self, account: &AccountId) -> bool {
+    let demo = match self.demo.as_mut() {
+      Some(demo) => demo,
+      None => return true,
+    };
+    if self
+      .app_user
+      .get(account)
+      .is_some_and(|user| user.id < demo.exempt_below)
+    {
+      return true;
+    }
+    let today = env::block_timestamp() / NANOS_PER_DAY;
+    if demo.day != today {
+      demo.day = today;
+      demo.used = 0;
+    }
+    if demo.used >= demo.daily_quota {
+      return false;
+    }
+    demo.used += 1;
+    true
+  }
+}
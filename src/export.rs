@@ -0,0 +1,77 @@
+use crate::*;
+use near_sdk::serde_json::Value;
+
+/*  Snapshot export for indexers.
+    Every collection can be enumerated page by page in a deterministic order (sorted by key),
+    so a new indexer can bootstrap a full snapshot without replaying the chain history.
+    View calls have no caller to check, and contract state is public anyway, so these are not gated.
+    Per-user records are exported as stored. Raw trend snapshots are left out so the export does not
+    hand out aggregates `population_trend` would suppress, but that is no protection for the users
+    behind them, whose records are all in the `Data` collection.
+*/
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Collection {
+  AppUser,
+  Data,
+  Grants,
+  UserEventSeq,
+  Receipts,
+  Processors,
+  ExcludedProcessors,
+  BandProofs,
+  FeatureFlags,
+  Usage,
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn export_collection_len(&self, collection: Collection) -> u64 {
+    let len = match collection {
+      Collection::AppUser => self.app_user.len(),
+      Collection::Data => self.data.len(),
+      Collection::Grants => self.grants.len(),
+      Collection::UserEventSeq => self.user_event_seq.len(),
+      Collection::Receipts => self.receipts.len(),
+      Collection::Processors => self.processors.len(),
+      Collection::ExcludedProcessors => self.excluded_processors.len(),
+      Collection::BandProofs => self.band_proofs.len(),
+      Collection::FeatureFlags => self.feature_flags.len(),
+      Collection::Usage => self.usage.len(),
+    };
+    len as u64
+  }
+
+  // Entries `from..from + limit` of a collection, each as {"key", "value"}
+  pub fn export_state_page(&self, collection: Collection, from: u64, limit: u64) -> Vec<Value> {
+    match collection {
+      Collection::AppUser => page(&self.app_user, from, limit),
+      Collection::Data => page(&self.data, from, limit),
+      Collection::Grants => page(&self.grants, from, limit),
+      Collection::UserEventSeq => page(&self.user_event_seq, from, limit),
+      Collection::Receipts => page(&self.receipts, from, limit),
+      Collection::Processors => page(&self.processors, from, limit),
+      Collection::ExcludedProcessors => page(&self.excluded_processors, from, limit),
+      Collection::BandProofs => page(&self.band_proofs, from, limit),
+      Collection::FeatureFlags => page(&self.feature_flags, from, limit),
+      Collection::Usage => page(&self.usage, from, limit),
+    }
+  }
+}
+
+fn page<K: Ord + std::hash::Hash + Serialize, V: Serialize>(
+  map: &HashMap<K, V>,
+  from: u64,
+  limit: u64,
+) -> Vec<Value> {
+  let mut keys: Vec<&K> = map.keys().collect();
+  keys.sort();
+  keys
+    .into_iter()
+    .skip(from as usize)
+    .take(limit as usize)
+    .map(|key| json!({ "key": key, "value": map[key] }))
+    .collect()
+}
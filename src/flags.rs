@@ -0,0 +1,67 @@
+use crate::*;
+
+/*  Runtime feature flags.
+    The owner can switch subsystems on, off, or on for a percentage of accounts without redeploying.
+    A flag that was never set leaves its subsystem enabled, so flags act as staged rollouts and
+    kill-switches. Percentage rollouts bucket accounts by hash; calls without an account (views)
+    only see a percentage flag as enabled at 100.
+*/
+
+pub const RESEARCH: &str = "research";
+pub const BAND_PROOFS: &str = "band_proofs";
+pub const THIRD_PARTY: &str = "third_party";
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureFlag {
+  Enabled(bool),
+  Percentage(u8),
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn set_feature_flag(&mut self, key: String, flag: FeatureFlag) {
+    self.track_call("set_feature_flag");
+    self.assert_owner("change feature flags");
+    if let FeatureFlag::Percentage(percentage) = flag {
+      require!(percentage <= 100, "Percentage must be between 0 and 100");
+    }
+    self.emit_event("feature_flag_updated", json!({ "key": key, "flag": flag }));
+    self.feature_flags.insert(key, flag);
+  }
+
+  pub fn remove_feature_flag(&mut self, key: String) {
+    self.track_call("remove_feature_flag");
+    self.assert_owner("change feature flags");
+    self.feature_flags.remove(&key);
+    self.emit_event("feature_flag_removed", json!({ "key": key }));
+  }
+
+  pub fn feature_flags(&self) -> HashMap<String, FeatureFlag> {
+    self.feature_flags.clone()
+  }
+
+  pub fn is_feature_enabled(&self, key: String, account: Option<AccountId>) -> bool {
+    match (self.feature_flags.get(&key), account) {
+      (None, _) => true,
+      (Some(FeatureFlag::Enabled(enabled)), _) => *enabled,
+      (Some(FeatureFlag::Percentage(percentage)), None) => *percentage >= 100,
+      (Some(FeatureFlag::Percentage(percentage)), Some(account)) => {
+        let hash = env::sha256_array(format!("{}:{}", key, account).as_bytes());
+        (u16::from_le_bytes([hash[0], hash[1]]) % 100) < *percentage as u16
+      }
+    }
+  }
+}
+
+impl Contract {
+  pub(crate) fn assert_feature(&self, key: &str, account: Option<&AccountId>) {
+    require!(
+      self.is_feature_enabled(key.to_string(), account.cloned()),
+      &format!("Feature {} is disabled", key)
+    );
+  }
+}
@@ -0,0 +1,139 @@
+use crate::*;
+
+/*  BMI formula variants.
+    Standard is weight / height^2. Trefethen's variant, 1.3 * weight / height^2.5, scales better for
+    very short and very tall people. Researchers can also set their own coefficient and exponent.
+    The owner picks the variant used by compute, and every stored record keeps the variant it used.
+    A candidate variant can first run in shadow mode: compute evaluates it next to the live one, still
+    returns the live result, and reports every divergence in BMI or category for analysis before
+    the candidate is promoted.
+*/
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Formula {
+  #[default]
+  Standard,
+  Trefethen,
+  Custom {
+    coefficient: f32,
+    exponent: f32,
+  },
+}
+
+impl Formula {
+  // BMI from weight in kilograms and height in meters
+  pub fn bmi(&self, weight: f32, height: f32) -> f32 {
+    match self {
+      Formula::Standard => weight / height.powi(2),
+      Formula::Trefethen => 1.3 * weight / height.powf(2.5),
+      Formula::Custom {
+        coefficient,
+        exponent,
+      } => coefficient * weight / height.powf(*exponent),
+    }
+  }
+
+  fn assert_valid(&self) {
+    if let Formula::Custom {
+      coefficient,
+      exponent,
+    } = self
+    {
+      require!(
+        *coefficient > 0.0 && *exponent > 0.0,
+        "Coefficient and exponent must be positive"
+      );
+    }
+  }
+}
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ShadowStats {
+  pub formula: Option<Formula>,
+  pub runs: u64,
+  pub divergences: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn set_formula(&mut self, formula: Formula) {
+    self.track_call("set_formula");
+    self.assert_owner("change the formula");
+    self.apply_formula(formula);
+  }
+
+  pub fn get_formula(&self) -> Formula {
+    self.formula
+  }
+
+  // Start shadowing a candidate formula, or stop with null; counters restart either way
+  pub fn set_shadow_formula(&mut self, formula: Option<Formula>) {
+    self.track_call("set_shadow_formula");
+    self.assert_owner("change the shadow formula");
+    if let Some(formula) = formula {
+      formula.assert_valid();
+    }
+    self.shadow = ShadowStats {
+      formula,
+      ..ShadowStats::default()
+    };
+    self.emit_event("shadow_formula_updated", json!({ "formula": formula }));
+  }
+
+  // Make the shadowed candidate the live formula
+  pub fn promote_shadow_formula(&mut self) {
+    self.track_call("promote_shadow_formula");
+    self.assert_owner("change the formula");
+    match self.shadow.formula {
+      Some(formula) => {
+        self.shadow = ShadowStats::default();
+        self.apply_formula(formula);
+      }
+      None => env::panic_str("No shadow formula to promote"),
+    }
+  }
+
+  pub fn shadow_stats(&self) -> ShadowStats {
+    self.shadow.clone()
+  }
+}
+
+impl Contract {
+  fn apply_formula(&mut self, formula: Formula) {
+    formula.assert_valid();
+    self.population_switch_formula(formula);
+    self.formula = formula;
+    self.emit_event("formula_updated", json!({ "formula": formula }));
+  }
+
+  // Evaluate the shadow formula next to the live result and report any divergence
+  pub(crate) fn run_shadow(&mut self, account: &AccountId, weight: f32, height: f32, bmi: f32) {
+    let formula = match self.shadow.formula {
+      Some(formula) => formula,
+      None => return,
+    };
+    let shadow_bmi = formula.bmi(weight, height);
+    self.shadow.runs += 1;
+    let (category, shadow_category) = (Category::of(bmi), Category::of(shadow_bmi));
+    if shadow_bmi.trunc() != bmi.trunc() || shadow_category != category {
+      self.shadow.divergences += 1;
+      self.emit_event_for(
+        account,
+        "shadow_divergence",
+        json!({
+          "bmi": bmi,
+          "category": category,
+          "shadow_bmi": shadow_bmi,
+          "shadow_category": shadow_category,
+        }),
+      );
+    }
+  }
+}
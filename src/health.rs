@@ -0,0 +1,101 @@
+use crate::*;
+
+/*  Contract self-check.
+    Recomputes the internal invariants from the stored collections so operators can detect
+    state corruption, e.g. after a migration, before users run into it.
+*/
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Check {
+  pub name: String,
+  pub ok: bool,
+  pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SelfCheck {
+  pub ok: bool,
+  pub checks: Vec<Check>,
+}
+
+impl Check {
+  fn new(name: &str, failures: usize, total: usize) -> Self {
+    Self {
+      name: name.to_string(),
+      ok: failures == 0,
+      detail: format!("{} of {} failing", failures, total),
+    }
+  }
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn self_check(&self) -> SelfCheck {
+    // User ids are handed out from a counter, so they must be unique and below it
+    let users = self.app_user.len();
+    let mut seen = std::collections::HashSet::new();
+    let bad_ids = self
+      .app_user
+      .values()
+      .filter(|user| user.id >= self.next_user_id || !seen.insert(user.id))
+      .count();
+
+    // Records are keyed by the account they belong to
+    let orphaned_data = self
+      .data
+      .iter()
+      .filter(|(key, data)| **key != data.uid)
+      .count();
+
+    // Revoking the last grant removes the entry, so empty ones are leftovers
+    let grant_sets: usize = self.grants.values().map(|grants| grants.len()).sum();
+    let empty_grants = self
+      .grants
+      .values()
+      .map(|grants| {
+        if grants.is_empty() {
+          1
+        } else {
+          grants
+            .values()
+            .filter(|grant| grant.scopes.is_empty())
+            .count()
+        }
+      })
+      .sum();
+
+    // Every event is attributed to one account, so per-account counts add up to the contract count
+    let user_events: u64 = self.user_event_seq.values().sum();
+    let bad_seq = usize::from(user_events != self.event_seq);
+
+    // Running population totals match a recount of the records computed with the live formula
+    let mut recount = Population::default();
+    for data in self.data.values() {
+      if data.formula == self.formula {
+        recount.record(data, registry::Change::Add);
+      }
+    }
+    let counts = |population: &Population| {
+      let mut counts = vec![(None, population.all.users, population.all.categories)];
+      for (confidence, aggregate) in &population.by_confidence {
+        counts.push((Some(*confidence), aggregate.users, aggregate.categories));
+      }
+      counts
+    };
+    let bad_population = usize::from(counts(&self.population) != counts(&recount));
+
+    let checks = vec![
+      Check::new("app_user_ids", bad_ids, users),
+      Check::new("data_keys", orphaned_data, self.data.len()),
+      Check::new("grants", empty_grants, grant_sets),
+      Check::new("event_seq", bad_seq, 1),
+      Check::new("population", bad_population, 1),
+    ];
+    SelfCheck {
+      ok: checks.iter().all(|check| check.ok),
+      checks,
+    }
+  }
+}
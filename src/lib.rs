@@ -1,8 +1,55 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, log, near_bindgen};
+use near_sdk::serde_json::json;
+use near_sdk::{assert_one_yocto, env, near_bindgen, require};
 use std::collections::HashMap;
 
+pub use crate::body::{BsaMethod, LbmMethod, Sex};
+pub use crate::canonical::{Canonical, CanonicalWriter};
+pub use crate::claims::{Claim, ClaimResult};
+pub use crate::closure::AccountExport;
+pub use crate::config::{DeploymentConfig, Units};
+pub use crate::consent::ConsentReceipt;
+pub use crate::demo::{DemoMode, Preview};
+pub use crate::export::Collection;
+pub use crate::flags::FeatureFlag;
+pub use crate::formula::{Formula, ShadowStats};
+pub use crate::health::{Check, SelfCheck};
+pub use crate::logger::Verbosity;
+pub use crate::metrics::MethodUsage;
+pub use crate::projection::{ProjectedWeek, Projection};
+pub use crate::proofs::BandProof;
+pub use crate::registry::{HealthMetric, METRICS};
+pub use crate::scopes::{Grant, Scope};
+pub use crate::storage::StorageUsage;
+pub use crate::trends::{Aggregate, EpochTrend, Population};
+
+mod body;
+mod canonical;
+mod claims;
+mod closure;
+mod config;
+mod consent;
+mod demo;
+mod export;
+mod flags;
+mod formula;
+mod health;
+mod logger;
+mod metrics;
+mod privacy;
+mod processors;
+mod projection;
+mod proofs;
+mod read_only;
+mod registry;
+mod scopes;
+#[cfg(test)]
+mod simulation;
+mod storage;
+mod sweep;
+mod trends;
+
 pub type AccountId = String;
 
 /*  Body Mass Index (BMI) is a value derieved from person's weight and height.
@@ -15,6 +62,7 @@ pub struct AppUser {
   id: u32,
   uid: AccountId,
   u_name: Option<String>,
+  sex: Option<Sex>,
 }
 
 impl AppUser {
@@ -23,6 +71,7 @@ impl AppUser {
       id,
       uid: env::signer_account_id().to_string(),
       u_name: Some(u_name),
+      sex: None,
     }
   }
 }
@@ -31,15 +80,63 @@ impl AppUser {
 #[serde(crate = "near_sdk::serde")]
 pub struct Data {
   uid: String,
+  weight: u32,
+  height: f32,
   bmi: f32,
+  source: Source,
+  confidence: Option<u8>,
+  receipt: Option<u64>,
+  formula: Formula,
 }
 
-impl Data {
-  pub fn new(uid: String, bmi: f32) -> Self {
-    Self { uid, bmi }
+/*  BMI calculatar indicate wheather person falls under healthy weight, underweight or overweight.
+    If a person's BMI is out of healthy range, their health risk may significantly increases.
+    BMI range for adults BMI: weight status Below 18.5: Underweight 18.5 - 24.9, Normal or healthy weight 25.0 - 29.9, Overweight 30.0 & above: Obese
+*/
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+  Underweight,
+  Normal,
+  Overweight,
+  Obese,
+}
+
+impl Category {
+  pub const ALL: [Category; 4] = [
+    Category::Underweight,
+    Category::Normal,
+    Category::Overweight,
+    Category::Obese,
+  ];
+
+  // Bands are half-open so values such as 24.95 still land in a category
+  pub fn of(bmi: f32) -> Self {
+    match bmi {
+      bmi if bmi < 18.5 => Category::Underweight,
+      bmi if bmi < 25.0 => Category::Normal,
+      bmi if bmi < 30.0 => Category::Overweight,
+      _other => Category::Obese,
+    }
   }
 }
 
+// Who produced a measurement, so trends can be restricted to verified data
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+  Manual,
+  Device,
+  Clinician,
+  Oracle,
+}
+
 // Get user consent to set bio security measures the data
 #[derive(Deserialize, Serialize, BorshDeserialize, BorshSerialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
@@ -62,12 +159,54 @@ impl Default for DataPermission {
   }
 }
 
+// Outcome of recording a measurement, so the public method can count its own failures
+pub(crate) struct Recorded {
+  pub bmi: i32,
+  // Storing was refused because the demo quota ran out
+  pub quota_exhausted: bool,
+}
+
+// Consent a measurement is stored under
+#[derive(Clone, Copy)]
+pub(crate) enum Consent<'a> {
+  // The user's own permit; storing mints a `store:measurements` receipt in their name
+  Permit(&'a DataPermission),
+  // A scoped write under the user's grant, whose receipt the record refers to
+  Grant(u64),
+}
+
+// The state is one unversioned borsh blob, and its layout has changed since the first release
+// (new fields here, in `Data` and in `AppUser`). There is no `migrate`, so upgrading an existing
+// deployment needs a fresh account; see "Upgrading" in the README.
 #[near_bindgen]
 #[derive(Default, BorshDeserialize, BorshSerialize, Clone, Debug)]
 pub struct Contract {
   uid: AccountId,
   app_user: HashMap<String, AppUser>,
   data: HashMap<String, Data>,
+  verbosity: Verbosity,
+  config: DeploymentConfig,
+  grants: HashMap<AccountId, HashMap<AccountId, Grant>>,
+  event_seq: u64,
+  user_event_seq: HashMap<AccountId, u64>,
+  population: Population,
+  trend: HashMap<u64, Population>,
+  min_cohort: u64,
+  noise: Option<privacy::Noise>,
+  consent_policy: near_sdk::json_types::Base58CryptoHash,
+  consent_validity_ns: u64,
+  next_receipt_id: u64,
+  receipts: HashMap<AccountId, Vec<ConsentReceipt>>,
+  processors: HashMap<AccountId, String>,
+  excluded_processors: HashMap<AccountId, Vec<AccountId>>,
+  band_proofs: HashMap<AccountId, Vec<near_sdk::json_types::Base58CryptoHash>>,
+  formula: Formula,
+  feature_flags: HashMap<String, FeatureFlag>,
+  shadow: ShadowStats,
+  demo: Option<DemoMode>,
+  usage: HashMap<String, MethodUsage>,
+  read_only: bool,
+  next_user_id: u32,
 }
 
 #[near_bindgen]
@@ -80,85 +219,130 @@ impl Contract {
       uid,
       data,
       app_user,
+      verbosity: Verbosity::default(),
+      config: DeploymentConfig::default(),
+      grants: HashMap::new(),
+      event_seq: 0,
+      user_event_seq: HashMap::new(),
+      population: Population::default(),
+      trend: HashMap::new(),
+      min_cohort: privacy::DEFAULT_MIN_COHORT,
+      noise: None,
+      consent_policy: Default::default(),
+      consent_validity_ns: consent::DEFAULT_CONSENT_VALIDITY_NS,
+      next_receipt_id: 0,
+      receipts: HashMap::new(),
+      processors: HashMap::new(),
+      excluded_processors: HashMap::new(),
+      band_proofs: HashMap::new(),
+      formula: Formula::default(),
+      feature_flags: HashMap::new(),
+      shadow: ShadowStats::default(),
+      demo: None,
+      usage: HashMap::new(),
+      read_only: false,
+      next_user_id: 0,
     }
   }
+
+  // Owner controls how much diagnostic output the contract logs
+  pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+    self.track_call("set_verbosity");
+    self.assert_owner("change the verbosity");
+    self.verbosity = verbosity;
+  }
+
+  pub fn get_verbosity(&self) -> Verbosity {
+    self.verbosity
+  }
+
   /*
       BMI calculation is based on a simple formula using a person's weight and height.
       The Formular for BMI= kg/m2 where kg is person's weight in kilograms and m2 is their height in meters squared.
       in simple fomart it would be BMI = (weight in kilograms)/(Heights in meters * Heights in meters)
+      The owner can switch to another formula variant, see `Formula`.
   */
 
   pub fn compute(&mut self, weight: u32, height: f32, permit: &DataPermission) -> i32 {
-    // let id = self.app_user.len() as u32;
-
-    let u_name = env::signer_account_id().to_string();
-
-    let height = height / 100.0;
-
-    // For example if a person's weight is 92  and height is 136 then BMI=  92/(1.36^2) = 50
-    let bmi = weight as f32 / height.powi(2);
-
-    // For better readability we return 32-bit signed integer type when dealing with conversion.
-    let n_bmi = ((bmi * 100f32).trunc() / 100.0) as i32;
-
-    /*  BMI calculatar indicate wheather person falls under healthy weight, underweight or overweight.
-        If a person's BMI is out of healthy range, their health risk may significantly increases.
-        BMI range for adults BMI: weight status Below 18.5: Underweight 18.5 - 24.9, Normal or healthy weight 25.0 - 29.9, Overweight 30.0 & above: Obese
-    */
-
-    match bmi {
-      bmi if bmi < 18.5 => log!("{} You are Underweight  ", u_name),
-      bmi if (18.5..=24.9).contains(&bmi) => log!("{} You are Underweight  ", u_name),
-      bmi if (25.0..=29.9).contains(&bmi) => log!("{} You are Underweight  ", u_name),
-      _other => log!("{} You are Obese  ", u_name),
-    }
-
-    log!("BMI: {}", n_bmi);
-
-    match permit.0 {
-      Some(_data) => {
-        if _data {
-          match self.data.get(&u_name) {
-            Some(_) => {
-              env::log_str("We've got your data😍😍");
-            }
-            None => {
-              env::log_str("Permission Accepted");
-
-              self
-                .data
-                .insert(u_name, Data::new(env::signer_account_id().to_string(), bmi));
-
-              env::log_str("BIOSECURITY MEASURES ARE IN EFFECT");
-            }
-          }
-        } else {
-          env::log_str("Kindly accept Permission to secure your Data");
-        }
-      }
-      None => (),
+    self.track_call("compute");
+    self.assert_writable();
+    let recorded = self.compute_for(
+      env::signer_account_id().to_string(),
+      weight,
+      height,
+      permit,
+      Source::Manual,
+      None,
+    );
+    if recorded.quota_exhausted {
+      self.track_failure("compute");
     }
-
-    n_bmi
+    recorded.bmi
   }
 
   pub fn set_user(&mut self, u_name: String) {
-    let uid = self.app_user.len() as u32;
+    self.track_call("set_user");
+    self.assert_writable();
+    let uid = self.next_user_id;
     let _app_user = env::signer_account_id().to_string();
     let current_user = self.app_user.get(&_app_user);
     match current_user {
-      Some(_) => env::log_str("The provided uid is already in use by an existing user"),
+      Some(_) => {
+        self.track_failure("set_user");
+        self.log_event(Verbosity::Info, "user_exists", json!({}));
+      }
       None => {
         self
           .app_user
           .insert(_app_user, AppUser::new_user(uid, u_name));
-        env::log_str("Data set successfully");
+        self.next_user_id += 1;
+        self.emit_event("user_set", json!({ "id": uid }));
       }
     }
   }
 
   // Get user data after saved
   pub fn get_data(&mut self, uid: String) -> Option<String> {
+    self.track_call("get_data");
+    let data = self.read_data(uid);
+    if data.is_none() {
+      self.track_failure("get_data");
+    }
+    data
+  }
+
+  // Deletes the caller's own record; like close_account it needs one yoctoNEAR from a full access key
+  #[payable]
+  pub fn delete_data(&mut self, permit: &DataPermission) {
+    assert_one_yocto();
+    self.track_call("delete_data");
+    self.assert_writable();
+    if let Some(_data) = permit.0 {
+      if _data {
+        let uid = env::predecessor_account_id().to_string();
+        match self.data.remove(&uid) {
+          Some(record) => {
+            self.population_remove(&record);
+            self.emit_event_for(&uid, "data_deleted", json!({ "uid": uid }));
+          }
+          None => {
+            self.track_failure("delete_data");
+            self.log_event(Verbosity::Debug, "data_not_found", json!({ "uid": uid }));
+          }
+        }
+      } else {
+        self.log_event(
+          Verbosity::Info,
+          "permission_required",
+          json!({ "action": "delete" }),
+        );
+      }
+    }
+  }
+}
+
+impl Contract {
+  pub(crate) fn read_data(&mut self, uid: String) -> Option<String> {
     let d = self.data.get(&uid);
     match d {
       Some(_data) => {
@@ -166,24 +350,127 @@ impl Contract {
         Some(msg)
       }
       None => {
-        env::log_str("No Data Found");
+        self.log_event(Verbosity::Debug, "data_not_found", json!({ "uid": uid }));
         None
       }
     }
   }
 
-  pub fn delete_data(&mut self, uid: String, permit: &DataPermission) {
-    match permit.0 {
-      Some(_data) => {
-        if _data {
-          self.data.remove(&uid);
-          env::log_str("Your Data Is Delete");
-        } else {
-          env::log_str("Kindly accept Permission to delete your Data");
+  // Compute and optionally store the BMI of `u_name`, who is either the signer or a scoped grantor
+  pub(crate) fn compute_for(
+    &mut self,
+    u_name: String,
+    weight: u32,
+    height: f32,
+    permit: &DataPermission,
+    source: Source,
+    confidence: Option<u8>,
+  ) -> Recorded {
+    let record = self.measure(u_name, weight, height, source, confidence);
+    self.record_measurement(record, Consent::Permit(permit))
+  }
+
+  // Build and validate the record of a measurement. Nothing is logged here, so a measurement that is
+  // rejected reverts without leaving an event behind
+  pub(crate) fn measure(
+    &self,
+    u_name: String,
+    weight: u32,
+    height: f32,
+    source: Source,
+    confidence: Option<u8>,
+  ) -> Data {
+    require!(
+      confidence.is_none_or(|confidence| confidence <= 100),
+      "Confidence must be between 0 and 100"
+    );
+    // For example if a person's weight is 92  and height is 136 then BMI=  92/(1.36^2) = 50
+    let formula = self.formula;
+    let bmi = formula.bmi(weight as f32, height / 100.0);
+    let record = Data {
+      uid: u_name,
+      weight,
+      height,
+      bmi,
+      source,
+      confidence,
+      receipt: None,
+      formula,
+    };
+    registry::assert_valid_record(&record);
+    record
+  }
+
+  // Report a validated measurement and store it when `consent` allows
+  pub(crate) fn record_measurement(&mut self, mut record: Data, consent: Consent) -> Recorded {
+    let u_name = record.uid.clone();
+    let bmi = record.bmi;
+    self.run_shadow(&u_name, record.weight as f32, record.height / 100.0, bmi);
+
+    // For better readability we return 32-bit signed integer type when dealing with conversion.
+    let n_bmi = ((bmi * 100f32).trunc() / 100.0) as i32;
+
+    let category = Category::of(bmi);
+    self.log_event_for(
+      &u_name,
+      Verbosity::Info,
+      "bmi_computed",
+      json!({ "bmi": n_bmi, "category": category }),
+    );
+
+    let permit = match consent {
+      Consent::Permit(permit) => permit.0,
+      Consent::Grant(_) => Some(true),
+    };
+    if let Some(_data) = permit {
+      if _data {
+        match self.data.get(&u_name) {
+          Some(_) => {
+            self.log_event_for(&u_name, Verbosity::Debug, "data_exists", json!({}));
+          }
+          None => {
+            if !self.take_demo_quota(&u_name) {
+              self.log_event_for(&u_name, Verbosity::Info, "demo_quota_exhausted", json!({}));
+              return Recorded {
+                bmi: n_bmi,
+                quota_exhausted: true,
+              };
+            }
+            self.emit_event_for(&u_name, "permission_accepted", json!({}));
+
+            self.population_add(&record);
+            record.receipt = Some(match consent {
+              Consent::Grant(receipt) => receipt,
+              Consent::Permit(_) => {
+                self.mint_receipt(&u_name, vec!["store:measurements".to_string()])
+              }
+            });
+            self.data.insert(u_name.clone(), record);
+
+            self.emit_event_for(&u_name, "data_secured", json!({}));
+          }
         }
+      } else {
+        self.log_event_for(
+          &u_name,
+          Verbosity::Info,
+          "permission_required",
+          json!({ "action": "store" }),
+        );
       }
-      None => (),
     }
+
+    Recorded {
+      bmi: n_bmi,
+      quota_exhausted: false,
+    }
+  }
+
+  pub(crate) fn assert_owner(&self, action: &str) {
+    require!(
+      env::predecessor_account_id().to_string() == self.uid,
+      &format!("Only the owner can {}", action)
+    );
   }
 }
 
@@ -198,9 +485,23 @@ mod test {
     AccountId::try_from(account.to_string()).expect("Invalid account")
   }
 
+  fn salt_hash(salt: &[u8]) -> near_sdk::json_types::Base58CryptoHash {
+    let hash: near_sdk::CryptoHash = env::sha256(salt).try_into().unwrap();
+    hash.into()
+  }
+
+  fn parse_log(line: &str) -> near_sdk::serde_json::Value {
+    let event = line
+      .strip_prefix("EVENT_JSON:")
+      .expect("Log should be an event");
+    near_sdk::serde_json::from_str(event).expect("Log should be JSON")
+  }
+
   fn get_context(predecessor: AccountId) -> VMContextBuilder {
     let mut builder = VMContextBuilder::new();
-    builder.signer_account_id(predecessor);
+    builder
+      .signer_account_id(predecessor.clone())
+      .predecessor_account_id(predecessor);
     builder
   }
 
@@ -255,7 +556,1077 @@ mod test {
     testing_env!(context.build());
     let mut _data = Contract::new(kherld.to_string());
     let permit = DataPermission::default();
-    let delete_test = _data.delete_data(kherld.to_string(), &permit);
-    assert_eq!((), delete_test);
+    _data.compute(45, 125.0, &permit);
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.attached_deposit(1).build());
+    _data.delete_data(&permit);
+    assert!(_data.get_data(kherld.to_string()).is_none());
+  }
+
+  #[test]
+  fn structured_log_test() {
+    let kherld = AccountId::new_unchecked("kherld.testnet".to_string());
+    let context = get_context(to_valid_account("kherld.testnet"));
+
+    testing_env!(context.build());
+    let mut _data = Contract::new(kherld.to_string());
+    _data.compute(45, 125.0, &DataPermission::default());
+    let logs = near_sdk::test_utils::get_logs();
+    let first = parse_log(&logs[0]);
+    assert_eq!(first["standard"], "near_bmi");
+    assert_eq!(first["event"], "bmi_computed");
+    assert_eq!(first["data"]["account"], "kherld.testnet");
+    assert_eq!(first["data"]["fields"]["category"], "overweight");
+
+    // Quiet drops diagnostics only
+    _data.set_verbosity(Verbosity::Quiet);
+    _data.compute(45, 125.0, &DataPermission::default());
+    assert_eq!(near_sdk::test_utils::get_logs().len(), logs.len());
+    _data.authorize("fitness.testnet".to_string(), vec![Scope::ReadLatest]);
+    let events: Vec<String> = near_sdk::test_utils::get_logs()[logs.len()..]
+      .iter()
+      .map(|line| parse_log(line)["event"].as_str().unwrap().to_string())
+      .collect();
+    assert_eq!(events, vec!["scopes_granted", "consent_receipt"]);
+  }
+
+  #[test]
+  #[should_panic(expected = "Only the owner can change the verbosity")]
+  fn set_verbosity_owner_only_test() {
+    let context = get_context(to_valid_account("random.testnet"));
+
+    testing_env!(context.build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_verbosity(Verbosity::Debug);
+  }
+
+  #[test]
+  fn config_test() {
+    let context = get_context(to_valid_account("kherld.testnet"));
+
+    testing_env!(context.build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    assert_eq!(_data.get_config(), DeploymentConfig::default());
+
+    let config = DeploymentConfig {
+      app_name: "Gym BMI".to_string(),
+      support_contact: Some("help@gym.example".to_string()),
+      default_language: "sw".to_string(),
+      default_units: Units::Imperial,
+    };
+    _data.set_config(config.clone());
+    assert_eq!(_data.get_config(), config);
+  }
+
+  #[test]
+  fn scoped_access_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.authorize(
+      "fitness.testnet".to_string(),
+      vec![Scope::ReadLatest, Scope::WriteMeasurements],
+    );
+    _data.register_processor(
+      "fitness.testnet".to_string(),
+      "fitness tracking".to_string(),
+    );
+
+    testing_env!(get_context(to_valid_account("fitness.testnet")).build());
+    assert_eq!(
+      28,
+      _data.scoped_compute(
+        "kherld.testnet".to_string(),
+        45,
+        125.0,
+        None,
+        "fitness tracking".to_string()
+      )
+    );
+    let latest = _data.scoped_get_data("kherld.testnet".to_string());
+    assert!(latest.unwrap().ends_with("kherld.testnet"));
+
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    _data.revoke("fitness.testnet".to_string());
+    assert!(_data
+      .scopes_of("kherld.testnet".to_string(), "fitness.testnet".to_string())
+      .is_empty());
+    // The withdrawn consent lapses, and is kept only because the stored record refers to it
+    let receipts = _data.consent_receipts_of("kherld.testnet".to_string());
+    assert_eq!(receipts.len(), 1);
+    assert!(receipts[0].lapsed);
+  }
+
+  #[test]
+  #[should_panic(expected = "Missing scope for this call")]
+  fn scoped_access_denied_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.authorize("fitness.testnet".to_string(), vec![Scope::ReadLatest]);
+    _data.register_processor(
+      "fitness.testnet".to_string(),
+      "fitness tracking".to_string(),
+    );
+
+    testing_env!(get_context(to_valid_account("fitness.testnet")).build());
+    _data.scoped_compute(
+      "kherld.testnet".to_string(),
+      45,
+      125.0,
+      None,
+      "fitness tracking".to_string(),
+    );
+  }
+
+  #[test]
+  fn authorize_through_contract_test() {
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    builder.predecessor_account_id(to_valid_account("game.testnet"));
+    testing_env!(builder.build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.authorize("evil.testnet".to_string(), vec![Scope::WriteMeasurements]);
+    assert!(_data
+      .scopes_of("kherld.testnet".to_string(), "evil.testnet".to_string())
+      .is_empty());
+    assert_eq!(
+      _data.scopes_of("game.testnet".to_string(), "evil.testnet".to_string()),
+      vec![Scope::WriteMeasurements]
+    );
+  }
+
+  #[test]
+  fn event_sequence_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_user("Kherld".to_owned());
+    testing_env!(get_context(to_valid_account("random.testnet")).build());
+    _data.set_user("Random".to_owned());
+    _data.compute(45, 125.0, &DataPermission::new(false));
+
+    let logs: Vec<near_sdk::serde_json::Value> = near_sdk::test_utils::get_logs()
+      .iter()
+      .map(|log| parse_log(log))
+      .collect();
+    let seqs: Vec<u64> = logs
+      .iter()
+      .map(|log| log["data"]["seq"].as_u64().unwrap())
+      .collect();
+    let user_seqs: Vec<u64> = logs
+      .iter()
+      .map(|log| log["data"]["user_seq"].as_u64().unwrap())
+      .collect();
+    assert_eq!(seqs, vec![2, 3, 4]);
+    assert_eq!(user_seqs, vec![1, 2, 3]);
+    assert_eq!(_data.event_seq(Some("kherld.testnet".to_string())), (4, 1));
+  }
+
+  #[test]
+  fn event_sequence_follows_data_owner_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.authorize(
+      "fitness.testnet".to_string(),
+      vec![Scope::WriteMeasurements],
+    );
+    _data.register_processor(
+      "fitness.testnet".to_string(),
+      "fitness tracking".to_string(),
+    );
+    let (_, before) = _data.event_seq(Some("kherld.testnet".to_string()));
+
+    testing_env!(get_context(to_valid_account("fitness.testnet")).build());
+    _data.scoped_compute(
+      "kherld.testnet".to_string(),
+      45,
+      125.0,
+      None,
+      "fitness tracking".to_string(),
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    let (_, after) = _data.event_seq(Some("kherld.testnet".to_string()));
+    assert_eq!(after - before, logs.len() as u64);
+    assert_eq!(_data.event_seq(Some("fitness.testnet".to_string())).1, 0);
+  }
+
+  #[test]
+  fn missing_record_events_follow_caller_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_verbosity(Verbosity::Debug);
+    _data.get_data("ghost.testnet".to_string());
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.attached_deposit(1).build());
+    _data.delete_data(&DataPermission::default());
+    assert_eq!(_data.event_seq(Some("kherld.testnet".to_string())), (2, 2));
+    assert_eq!(_data.export_collection_len(Collection::UserEventSeq), 1);
+  }
+
+  #[test]
+  fn export_state_page_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    for account in ["carol.testnet", "alice.testnet", "bob.testnet"] {
+      testing_env!(get_context(to_valid_account(account)).build());
+      _data.set_user(account.to_owned());
+    }
+
+    assert_eq!(_data.export_collection_len(Collection::AppUser), 3);
+    let first = _data.export_state_page(Collection::AppUser, 0, 2);
+    let rest = _data.export_state_page(Collection::AppUser, 2, 2);
+    let keys: Vec<&str> = first
+      .iter()
+      .chain(rest.iter())
+      .map(|entry| entry["key"].as_str().unwrap())
+      .collect();
+    assert_eq!(keys, vec!["alice.testnet", "bob.testnet", "carol.testnet"]);
+    assert_eq!(first[0]["value"]["u_name"], "alice.testnet");
+  }
+
+  #[test]
+  fn self_check_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_user("Kherld".to_owned());
+    _data.compute(45, 125.0, &DataPermission::default());
+    assert!(_data.self_check().ok);
+
+    _data.data.insert(
+      "ghost.testnet".to_string(),
+      Data {
+        uid: "kherld.testnet".to_string(),
+        weight: 45,
+        height: 150.0,
+        bmi: 20.0,
+        source: Source::Manual,
+        confidence: None,
+        receipt: None,
+        formula: Formula::Standard,
+      },
+    );
+    let report = _data.self_check();
+    assert!(!report.ok);
+    let failing: Vec<&str> = report
+      .checks
+      .iter()
+      .filter(|check| !check.ok)
+      .map(|check| check.name.as_str())
+      .collect();
+    assert_eq!(failing, vec!["data_keys", "population"]);
+  }
+
+  #[test]
+  fn storage_usage_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    assert_eq!(
+      _data.storage_usage_of("kherld.testnet".to_string()),
+      StorageUsage::default()
+    );
+
+    _data.compute(45, 125.0, &DataPermission::default());
+    let usage = _data.storage_usage_of("kherld.testnet".to_string());
+    // key and uid are both the 14 byte account name with a 4 byte length prefix,
+    // plus weight, height and BMI, the source tag, an empty confidence, the receipt id and the formula tag
+    assert_eq!(usage.data, 18 + 18 + 4 + 4 + 4 + 1 + 1 + 9 + 1);
+    assert_eq!(usage.app_user, 0);
+    assert!(usage.receipts > 0);
+    assert_eq!(usage.total, usage.data + usage.events + usage.receipts);
+
+    _data.exclude_processor("fitness.testnet".to_string());
+    let usage = _data.storage_usage_of("kherld.testnet".to_string());
+    // key, then a list of one 15 byte account name
+    assert_eq!(usage.excluded_processors, 18 + 4 + 19);
+  }
+
+  #[test]
+  fn measurement_source_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.authorize("scale.testnet".to_string(), vec![Scope::WriteMeasurements]);
+    _data.register_processor("scale.testnet".to_string(), "smart scale".to_string());
+
+    testing_env!(get_context(to_valid_account("scale.testnet")).build());
+    _data.scoped_compute(
+      "kherld.testnet".to_string(),
+      45,
+      125.0,
+      Some(90),
+      "smart scale".to_string(),
+    );
+    let record = &_data.data["kherld.testnet"];
+    assert_eq!(record.source, Source::Device);
+    assert_eq!(record.confidence, Some(90));
+    // Stored under the user's grant, not a consent minted during the processor's call
+    let grant = &_data.grants["kherld.testnet"]["scale.testnet"];
+    assert_eq!(record.receipt, Some(grant.receipt));
+    assert_eq!(
+      _data
+        .consent_receipts_of("kherld.testnet".to_string())
+        .len(),
+      1
+    );
+  }
+
+  #[test]
+  fn population_trend_test() {
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(1);
+    _data.compute(45, 125.0, &DataPermission::default());
+
+    builder
+      .signer_account_id(to_valid_account("random.testnet"))
+      .block_timestamp(2 * trends::TREND_EPOCH_NS);
+    testing_env!(builder.build());
+    _data.compute(52, 127.0, &DataPermission::default());
+    _data.compute(60, 170.0, &DataPermission::new(false));
+
+    let trend = _data.population_trend(0, 2, None);
+    let users: Vec<u64> = trend.iter().map(|epoch| epoch.users).collect();
+    assert_eq!(users, vec![1, 1, 2]);
+    assert!((trend[0].means["bmi"] - 28.8).abs() < 0.01);
+    assert_eq!(trend[2].proportions[2], (Category::Overweight, 0.5));
+    assert_eq!(trend[2].proportions[3], (Category::Obese, 0.5));
+
+    builder
+      .predecessor_account_id(to_valid_account("random.testnet"))
+      .attached_deposit(1);
+    testing_env!(builder.build());
+    _data.delete_data(&DataPermission::default());
+    assert_eq!(_data.population_trend(2, 2, None)[0].users, 1);
+  }
+
+  #[test]
+  fn population_trend_confidence_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(1);
+    for (account, confidence) in [
+      ("alice.testnet", None),
+      ("bob.testnet", Some(80)),
+      ("carol.testnet", Some(95)),
+    ] {
+      _data.compute_for(
+        account.to_string(),
+        60,
+        170.0,
+        &DataPermission::default(),
+        Source::Device,
+        confidence,
+      );
+    }
+
+    assert_eq!(_data.population_trend(0, 0, None)[0].users, 3);
+    assert_eq!(_data.population_trend(0, 0, Some(80))[0].users, 2);
+    assert_eq!(_data.population_trend(0, 0, Some(90))[0].users, 1);
+    assert_eq!(_data.population_trend(0, 0, Some(100))[0].users, 0);
+    assert!(_data.self_check().ok);
+
+    let mut builder = get_context(to_valid_account("carol.testnet"));
+    testing_env!(builder.attached_deposit(1).build());
+    _data.delete_data(&DataPermission::default());
+    assert_eq!(_data.population_trend(0, 0, Some(90))[0].users, 0);
+    assert_eq!(_data.population_trend(0, 0, None)[0].users, 2);
+  }
+
+  #[test]
+  fn small_cohort_suppressed_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(3);
+    for account in ["alice.testnet", "bob.testnet", "carol.testnet"] {
+      testing_env!(get_context(to_valid_account(account)).build());
+      _data.compute(90, 160.0, &DataPermission::default());
+      let trend = &_data.population_trend(0, 0, None)[0];
+      if _data.data.len() < 3 {
+        assert!(trend.suppressed);
+        assert_eq!(trend.users, 0);
+        assert!(trend.means.is_empty());
+        assert!(trend.proportions.is_empty());
+      } else {
+        assert!(!trend.suppressed);
+        assert_eq!(trend.users, 3);
+      }
+    }
+  }
+
+  #[test]
+  fn small_change_suppressed_test() {
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(2);
+    let weekly = [
+      vec!["alice.testnet", "bob.testnet"],
+      vec!["carol.testnet"],
+      vec!["dave.testnet"],
+    ];
+    for (week, accounts) in weekly.iter().enumerate() {
+      for account in accounts {
+        builder
+          .signer_account_id(to_valid_account(account))
+          .block_timestamp(week as u64 * trends::TREND_EPOCH_NS);
+        testing_env!(builder.build());
+        _data.compute(60, 170.0, &DataPermission::default());
+      }
+    }
+
+    // Week 1 only adds carol, so releasing it next to week 0 would reveal her BMI
+    let trend = _data.population_trend(0, 2, None);
+    let users: Vec<u64> = trend.iter().map(|epoch| epoch.users).collect();
+    assert_eq!(users, vec![2, 0, 4]);
+    assert!(trend[1].suppressed);
+    assert!(_data.population_trend(1, 1, None)[0].suppressed);
+
+    // The same holds when one view is filtered and the other is not
+    builder
+      .signer_account_id(to_valid_account("erin.testnet"))
+      .block_timestamp(5 * trends::TREND_EPOCH_NS);
+    testing_env!(builder.build());
+    _data.compute_for(
+      "erin.testnet".to_string(),
+      60,
+      170.0,
+      &DataPermission::default(),
+      Source::Device,
+      Some(90),
+    );
+    assert!(_data.population_trend(5, 5, None)[0].suppressed);
+    assert!(_data.population_trend(5, 5, Some(90))[0].suppressed);
+  }
+
+  #[test]
+  fn small_tier_folded_test() {
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(3);
+    for (week, accounts) in [
+      (0, ["alice", "bob", "carol"]),
+      (1, ["dave", "erin", "frank"]),
+    ] {
+      testing_env!(builder
+        .block_timestamp(week * trends::TREND_EPOCH_NS)
+        .build());
+      for account in accounts {
+        _data.compute_for(
+          format!("{}.testnet", account),
+          60,
+          170.0,
+          &DataPermission::default(),
+          Source::Manual,
+          None,
+        );
+      }
+      // One device reading on its own confidence tier
+      if week == 0 {
+        _data.compute_for(
+          "scale.testnet".to_string(),
+          60,
+          170.0,
+          &DataPermission::default(),
+          Source::Device,
+          Some(95),
+        );
+      }
+    }
+
+    let users: Vec<u64> = _data
+      .population_trend(0, 1, None)
+      .iter()
+      .map(|epoch| epoch.users)
+      .collect();
+    assert_eq!(users, vec![4, 7]);
+    assert!(_data
+      .population_trend(0, 1, Some(90))
+      .iter()
+      .all(|epoch| epoch.suppressed));
+  }
+
+  #[test]
+  fn population_noise_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(1);
+    for n in 0..50 {
+      testing_env!(get_context(to_valid_account(&format!("user{}.testnet", n))).build());
+      _data.compute(70, 170.0, &DataPermission::default());
+    }
+    let exact = _data.population_trend(0, 0, None);
+
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.block_timestamp(3 * trends::TREND_EPOCH_NS).build());
+    _data.set_noise(Some(0.5));
+    let noisy = _data.population_trend(0, 3, None);
+    // Same epoch, same answer
+    assert_eq!(noisy, _data.population_trend(0, 3, None));
+    // Epochs without writes repeat the noise drawn for the last write, so it can not be averaged away
+    assert!(noisy
+      .iter()
+      .all(|epoch| epoch.users == noisy[0].users && epoch.means == noisy[0].means));
+    assert!((noisy[0].users as i64 - exact[0].users as i64).abs() < 30);
+
+    _data.set_noise(None);
+    assert_eq!(_data.population_trend(0, 0, None), exact);
+  }
+
+  #[test]
+  #[should_panic(expected = "to_epoch must not be in the future")]
+  fn future_trend_rejected_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let _data = Contract::new("kherld.testnet".to_string());
+    _data.population_trend(0, 1, None);
+  }
+
+  #[test]
+  fn outlier_bmi_clamped_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(1);
+    _data.compute(300, 100.0, &DataPermission::default());
+    assert_eq!(_data.population_trend(0, 0, None)[0].means["bmi"], 60.0);
+  }
+
+  #[test]
+  fn consent_receipt_test() {
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.block_timestamp(1_000).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    let policy = near_sdk::json_types::Base58CryptoHash::from(env::sha256_array(b"policy v2"));
+    _data.set_consent_policy(policy, 5_000);
+
+    _data.compute(45, 125.0, &DataPermission::default());
+    _data.authorize("fitness.testnet".to_string(), vec![Scope::ReadLatest]);
+    let receipts = _data.consent_receipts_of("kherld.testnet".to_string());
+    assert_eq!(receipts.len(), 2);
+    assert_eq!(receipts[0].policy_hash, policy);
+    assert_eq!(receipts[0].scopes, vec!["store:measurements"]);
+    assert_eq!(receipts[0].expires_at, 6_000);
+    assert_eq!(receipts[1].scopes, vec!["read:latest@fitness.testnet"]);
+    assert_eq!(_data.data["kherld.testnet"].receipt, Some(receipts[0].id));
+    assert_eq!(
+      _data.consent_receipt("kherld.testnet".to_string(), receipts[1].id),
+      Some(receipts[1].clone())
+    );
+
+    // Re-authorizing supersedes the grant's receipt instead of adding one
+    _data.authorize("fitness.testnet".to_string(), vec![Scope::ReadLatest]);
+    let reissued = _data.consent_receipts_of("kherld.testnet".to_string());
+    assert_eq!(reissued.len(), 2);
+    assert_eq!(reissued[0], receipts[0]);
+    assert_eq!(
+      _data.grants["kherld.testnet"]["fitness.testnet"].receipt,
+      reissued[1].id
+    );
+    assert_ne!(reissued[1].id, receipts[1].id);
+  }
+
+  #[test]
+  #[should_panic(expected = "Caller is not a registered processor for this purpose")]
+  fn processor_purpose_mismatch_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.authorize("scale.testnet".to_string(), vec![Scope::WriteMeasurements]);
+    _data.register_processor("scale.testnet".to_string(), "smart scale".to_string());
+
+    testing_env!(get_context(to_valid_account("scale.testnet")).build());
+    _data.scoped_compute(
+      "kherld.testnet".to_string(),
+      45,
+      125.0,
+      None,
+      "advertising".to_string(),
+    );
+  }
+
+  #[test]
+  #[should_panic(expected = "Processor is excluded by this user")]
+  fn processor_excluded_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.authorize("scale.testnet".to_string(), vec![Scope::WriteMeasurements]);
+    _data.register_processor("scale.testnet".to_string(), "smart scale".to_string());
+    _data.exclude_processor("scale.testnet".to_string());
+
+    testing_env!(get_context(to_valid_account("scale.testnet")).build());
+    _data.scoped_compute(
+      "kherld.testnet".to_string(),
+      45,
+      125.0,
+      None,
+      "smart scale".to_string(),
+    );
+  }
+
+  #[test]
+  fn sweep_expired_test() {
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.block_timestamp(0).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_consent_policy(Default::default(), 100);
+    _data.compute(45, 125.0, &DataPermission::default());
+    _data.authorize("fitness.testnet".to_string(), vec![Scope::ReadLatest]);
+    assert_eq!(_data.sweep_expired(10), 0);
+
+    testing_env!(builder.block_timestamp(100).build());
+    assert!(_data
+      .scopes_of("kherld.testnet".to_string(), "fitness.testnet".to_string())
+      .is_empty());
+    assert_eq!(_data.sweep_expired(1), 1);
+    assert_eq!(_data.sweep_expired(10), 2);
+    assert_eq!(_data.sweep_expired(10), 0);
+    // Only the receipt of the stored record is kept
+    let receipts = _data.consent_receipts_of("kherld.testnet".to_string());
+    assert_eq!(receipts.len(), 1);
+    assert!(receipts[0].lapsed);
+    assert_eq!(_data.data["kherld.testnet"].receipt, Some(receipts[0].id));
+    assert!(_data.grants.is_empty());
+
+    builder.attached_deposit(1);
+    testing_env!(builder.build());
+    _data.delete_data(&DataPermission::default());
+    assert_eq!(_data.sweep_expired(10), 1);
+    assert!(_data.receipts.is_empty());
+  }
+
+  #[test]
+  fn band_proof_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.compute(60, 170.0, &DataPermission::default());
+    let salt = b"kept on the client".to_vec();
+    let proof = _data.prove_band(salt_hash(&salt));
+
+    let account = "kherld.testnet".to_string();
+    assert!(_data.verify_band_proof(
+      account.clone(),
+      Category::Normal,
+      proof.issued_at,
+      salt.clone().into()
+    ));
+    assert!(!_data.verify_band_proof(
+      account.clone(),
+      Category::Overweight,
+      proof.issued_at,
+      salt.clone().into()
+    ));
+    assert!(!_data.verify_band_proof(
+      account,
+      Category::Normal,
+      proof.issued_at,
+      b"guessed".to_vec().into()
+    ));
+    assert!(!_data.verify_band_proof(
+      "random.testnet".to_string(),
+      Category::Normal,
+      proof.issued_at,
+      salt.into()
+    ));
+  }
+
+  #[test]
+  fn band_proof_limit_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.compute(60, 170.0, &DataPermission::default());
+    for n in 0..proofs::MAX_BAND_PROOFS + 2 {
+      _data.prove_band(salt_hash(&[n as u8]));
+    }
+    assert_eq!(
+      _data.band_proofs["kherld.testnet"].len(),
+      proofs::MAX_BAND_PROOFS
+    );
+  }
+
+  #[test]
+  fn band_proof_through_contract_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.compute(60, 170.0, &DataPermission::default());
+    testing_env!(get_context(to_valid_account("game.testnet")).build());
+    _data.compute(90, 170.0, &DataPermission::default());
+
+    // A contract the user calls proves its own band, not the user's
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    builder.predecessor_account_id(to_valid_account("game.testnet"));
+    testing_env!(builder.build());
+    _data.prove_band(salt_hash(b"chosen by the game"));
+    assert!(!_data.band_proofs.contains_key("kherld.testnet"));
+    assert_eq!(_data.band_proofs["game.testnet"].len(), 1);
+  }
+
+  #[test]
+  fn verify_claim_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.compute(60, 170.0, &DataPermission::default());
+    _data.authorize("insurer.testnet".to_string(), vec![Scope::VerifyClaims]);
+    let receipt = _data.data["kherld.testnet"].receipt;
+
+    testing_env!(get_context(to_valid_account("insurer.testnet")).build());
+    let account = "kherld.testnet".to_string();
+    let normal = _data.verify_claim(account.clone(), Claim::InCategory(Category::Normal));
+    assert_eq!(
+      normal,
+      ClaimResult {
+        holds: true,
+        proof: receipt
+      }
+    );
+    assert!(
+      !_data
+        .verify_claim(account.clone(), Claim::InCategory(Category::Obese))
+        .holds
+    );
+    assert!(_data.verify_claim(account, Claim::ConsentCurrent).holds);
+  }
+
+  #[test]
+  fn formula_variant_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_formula(Formula::Trefethen);
+    // 1.3 * 45 / 1.25^2.5 = 33.5
+    assert_eq!(33, _data.compute(45, 125.0, &DataPermission::default()));
+    assert_eq!(_data.data["kherld.testnet"].formula, Formula::Trefethen);
+
+    _data.set_formula(Formula::Custom {
+      coefficient: 1.0,
+      exponent: 2.0,
+    });
+    assert_eq!(28, _data.compute(45, 125.0, &DataPermission::new(false)));
+    // Only records of the live formula are aggregated
+    assert_eq!(_data.population.all.users, 0);
+    assert!(_data.self_check().ok);
+
+    _data.set_formula(Formula::Trefethen);
+    assert_eq!(_data.population.all.users, 1);
+    assert!(_data.self_check().ok);
+  }
+
+  #[test]
+  fn body_composition_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    let account = "kherld.testnet".to_string();
+    assert_eq!(
+      _data.compute_bsa(account.clone(), BsaMethod::Mosteller),
+      None
+    );
+
+    _data.compute(64, 180.0, &DataPermission::default());
+    // sqrt(64 * 180 / 3600) = 1.789
+    let mosteller = _data
+      .compute_bsa(account.clone(), BsaMethod::Mosteller)
+      .unwrap();
+    assert!((mosteller - 1.789).abs() < 0.001);
+    let du_bois = _data
+      .compute_bsa(account.clone(), BsaMethod::DuBois)
+      .unwrap();
+    assert!((du_bois - 1.816).abs() < 0.001);
+
+    // Lean body mass needs the sex from the profile
+    assert_eq!(_data.compute_lbm(account.clone(), LbmMethod::Boer), None);
+    _data.set_user("Kherld".to_owned());
+    _data.set_sex(Sex::Male);
+    // 0.407 * 64 + 0.267 * 180 - 19.2 = 54.908
+    let boer = _data.compute_lbm(account.clone(), LbmMethod::Boer).unwrap();
+    assert!((boer - 54.908).abs() < 0.01);
+    let hume = _data.compute_lbm(account, LbmMethod::Hume).unwrap();
+    assert!((hume - 52.537).abs() < 0.01);
+  }
+
+  #[test]
+  fn set_sex_through_contract_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_user("Kherld".to_owned());
+    testing_env!(get_context(to_valid_account("game.testnet")).build());
+    _data.set_user("Game".to_owned());
+
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    builder.predecessor_account_id(to_valid_account("game.testnet"));
+    testing_env!(builder.build());
+    _data.set_sex(Sex::Female);
+    assert_eq!(_data.app_user["kherld.testnet"].sex, None);
+    assert_eq!(_data.app_user["game.testnet"].sex, Some(Sex::Female));
+  }
+
+  #[test]
+  fn projection_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    let account = "kherld.testnet".to_string();
+    assert!(_data.project(account.clone(), -0.5, 10).is_none());
+
+    // 80 kg at 1.70 m is 27.7, Normal starts below 72.25 kg
+    _data.compute(80, 170.0, &DataPermission::default());
+    let projection = _data.project(account, -0.5, 20).unwrap();
+    assert_eq!(projection.series.len(), 21);
+    assert_eq!(projection.series[0].category, Category::Overweight);
+    assert_eq!(projection.series[20].weight, 70.0);
+    assert_eq!(projection.transitions.len(), 1);
+    assert_eq!(projection.transitions[0].week, 16);
+    assert_eq!(projection.transitions[0].category, Category::Normal);
+  }
+
+  #[test]
+  fn feature_flags_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    assert!(_data.is_feature_enabled("research".to_string(), None));
+
+    _data.set_feature_flag("research".to_string(), FeatureFlag::Percentage(50));
+    assert!(!_data.is_feature_enabled("research".to_string(), None));
+    let enabled = (0..200)
+      .filter(|n| {
+        _data.is_feature_enabled("research".to_string(), Some(format!("user{}.testnet", n)))
+      })
+      .count();
+    assert!(enabled > 60 && enabled < 140, "{} of 200 enabled", enabled);
+
+    _data.set_feature_flag("research".to_string(), FeatureFlag::Enabled(true));
+    assert_eq!(_data.population_trend(0, 0, None).len(), 1);
+    let flags = _data.export_state_page(Collection::FeatureFlags, 0, 10);
+    assert_eq!(flags[0]["key"], "research");
+  }
+
+  #[test]
+  #[should_panic(expected = "Feature research is disabled")]
+  fn feature_kill_switch_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_feature_flag("research".to_string(), FeatureFlag::Enabled(false));
+    _data.population_trend(0, 0, None);
+  }
+
+  #[test]
+  fn shadow_formula_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_shadow_formula(Some(Formula::Trefethen));
+
+    // Live result is returned, Trefethen gives 33.5 instead of 28.8
+    assert_eq!(28, _data.compute(45, 125.0, &DataPermission::default()));
+    // At 1.69 m both formulas agree on 22.7
+    _data.compute(65, 169.0, &DataPermission::new(false));
+    let stats = _data.shadow_stats();
+    assert_eq!((stats.runs, stats.divergences), (2, 1));
+    assert_eq!(_data.data["kherld.testnet"].formula, Formula::Standard);
+
+    _data.promote_shadow_formula();
+    assert_eq!(_data.get_formula(), Formula::Trefethen);
+    assert_eq!(_data.shadow_stats(), ShadowStats::default());
+  }
+
+  #[test]
+  fn canonical_hash_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    let account = "kherld.testnet".to_string();
+    assert!(_data.hash_record(account.clone()).is_none());
+
+    _data.compute(45, 125.0, &DataPermission::default());
+    let hash = _data.hash_record(account.clone()).unwrap();
+    assert_eq!(hash, _data.hash_record(account.clone()).unwrap());
+
+    // Float noise below the fixed-point resolution does not change the encoding
+    let mut record = _data.data[&account].clone();
+    record.bmi += 0.000001;
+    assert_eq!(record.canonical_hash(), hash);
+    record.bmi += 0.01;
+    assert_ne!(record.canonical_hash(), hash);
+
+    _data.set_user("Kherld".to_owned());
+    let profile = _data.hash_profile(account.clone()).unwrap();
+    _data.set_sex(Sex::Female);
+    assert_ne!(_data.hash_profile(account).unwrap(), profile);
+  }
+
+  #[test]
+  fn demo_mode_test() {
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_user("Kherld".to_owned());
+    _data.set_demo_mode(Some(1));
+    assert_eq!(_data.preview(45, 125.0).category, Category::Overweight);
+
+    // Registered users do not draw from the quota
+    _data.compute(45, 125.0, &DataPermission::default());
+    for account in ["alice.testnet", "bob.testnet"] {
+      testing_env!(get_context(to_valid_account(account)).build());
+      _data.compute(45, 125.0, &DataPermission::default());
+    }
+    assert!(_data.data.contains_key("alice.testnet"));
+    assert!(!_data.data.contains_key("bob.testnet"));
+
+    // Registering during the demo does not exempt an account
+    testing_env!(get_context(to_valid_account("carol.testnet")).build());
+    _data.set_user("Carol".to_owned());
+    _data.compute(45, 125.0, &DataPermission::default());
+    assert!(!_data.data.contains_key("carol.testnet"));
+
+    // The quota refills the next day
+    builder
+      .signer_account_id(to_valid_account("bob.testnet"))
+      .block_timestamp(1_000_000_000 * 60 * 60 * 24);
+    testing_env!(builder.build());
+    _data.compute(45, 125.0, &DataPermission::default());
+    assert!(_data.data.contains_key("bob.testnet"));
+  }
+
+  #[test]
+  fn usage_metrics_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_user("Kherld".to_owned());
+    _data.set_user("Kherld".to_owned());
+    _data.compute(45, 125.0, &DataPermission::default());
+    _data.get_data("random.testnet".to_string());
+
+    let usage = _data.usage_metrics();
+    let get = |method: &str| usage.get(method).cloned().unwrap_or_default();
+    assert_eq!(
+      get("set_user"),
+      MethodUsage {
+        calls: 2,
+        failures: 1
+      }
+    );
+    assert_eq!(
+      get("compute"),
+      MethodUsage {
+        calls: 1,
+        failures: 0
+      }
+    );
+    assert_eq!(
+      get("get_data"),
+      MethodUsage {
+        calls: 1,
+        failures: 1
+      }
+    );
+    assert_eq!(get("delete_data"), MethodUsage::default());
+    assert_eq!(
+      _data.export_collection_len(Collection::Usage),
+      _data.usage_metrics().len() as u64
+    );
+  }
+
+  #[test]
+  fn read_only_keeps_reads_live_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.compute(60, 170.0, &DataPermission::default());
+    _data.set_read_only(true);
+
+    let account = "kherld.testnet".to_string();
+    assert!(_data.get_data(account.clone()).is_some());
+    assert_eq!(_data.export_collection_len(Collection::Data), 1);
+    assert!(_data.hash_record(account).is_some());
+    let proof = _data.prove_band(salt_hash(b"salt"));
+    assert!(_data.verify_band_proof(
+      "kherld.testnet".to_string(),
+      Category::Normal,
+      proof.issued_at,
+      b"salt".to_vec().into()
+    ));
+  }
+
+  #[test]
+  #[should_panic(expected = "Contract is in read-only mode")]
+  fn read_only_blocks_writes_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_read_only(true);
+    _data.compute(60, 170.0, &DataPermission::default());
+  }
+
+  #[test]
+  fn close_account_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_user("Kherld".to_owned());
+    _data.compute(60, 170.0, &DataPermission::default());
+    _data.authorize("fitness.testnet".to_string(), vec![Scope::ReadLatest]);
+    _data.prove_band(salt_hash(b"salt"));
+
+    let mut builder = get_context(to_valid_account("kherld.testnet"));
+    testing_env!(builder.attached_deposit(1).build());
+    let export = _data.close_account();
+    assert_eq!(export.profile.unwrap().u_name, Some("Kherld".to_string()));
+    assert_eq!(export.record.unwrap().weight, 60);
+    assert!(export.grants.contains_key("fitness.testnet"));
+    assert_eq!(export.receipts.len(), 2);
+    assert_eq!(export.band_proofs.len(), 1);
+
+    let account = "kherld.testnet".to_string();
+    assert_eq!(
+      _data.storage_usage_of(account.clone()).total,
+      _data.storage_usage_of(account).events
+    );
+    assert!(_data.self_check().ok);
+
+    // A new user never reuses the closed account's id
+    testing_env!(get_context(to_valid_account("random.testnet")).build());
+    _data.set_user("Random".to_owned());
+    assert_eq!(_data.app_user["random.testnet"].id, 1);
+  }
+
+  #[test]
+  #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+  fn close_account_requires_deposit_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.close_account();
+  }
+
+  #[test]
+  fn metric_registry_test() {
+    let names: Vec<&str> = METRICS.iter().map(|metric| metric.name()).collect();
+    assert_eq!(names, vec!["weight", "height", "bmi"]);
+    assert_eq!(registry::Bmi.classify(27.0), Some(Category::Overweight));
+    assert_eq!(registry::Weight.classify(80.0), None);
+
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_min_cohort(1);
+    _data.compute(60, 170.0, &DataPermission::default());
+    _data.compute_for(
+      "random.testnet".to_string(),
+      80,
+      170.0,
+      &DataPermission::default(),
+      Source::Manual,
+      None,
+    );
+    assert_eq!(_data.population_trend(0, 0, None)[0].means["weight"], 70.0);
+  }
+
+  #[test]
+  #[should_panic(expected = "Invalid height")]
+  fn metric_registry_rejects_negative_height_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.compute(60, -170.0, &DataPermission::default());
+  }
+
+  #[test]
+  #[should_panic(expected = "Invalid height")]
+  fn preview_rejects_invalid_height_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.set_demo_mode(Some(1));
+    _data.preview(45, -125.0);
+  }
+
+  #[test]
+  #[should_panic(expected = "Invalid weight")]
+  fn metric_registry_rejects_zero_weight_test() {
+    testing_env!(get_context(to_valid_account("kherld.testnet")).build());
+    let mut _data = Contract::new("kherld.testnet".to_string());
+    _data.compute(0, 170.0, &DataPermission::default());
   }
 }
@@ -0,0 +1,95 @@
+use crate::*;
+use near_sdk::serde_json::{json, Value};
+
+/*  Structured events and diagnostics.
+    Every log line is a NEP-297 event, `EVENT_JSON:` followed by {"standard", "version", "event", "data"},
+    with data {"account", "seq", "user_seq", "fields"}, so log scrapers can parse them reliably. `account`
+    is the user whose data the event is about, which for scoped writes and sweeps is not the signer.
+    `seq` increments for every emitted event of the contract and `user_seq` for every event about that
+    account, letting indexers detect gaps and deduplicate.
+    Audit and lifecycle events (state changes, scoped access, consent, closure, shadow divergences) are
+    always emitted. The owner's verbosity level only decides which diagnostics, such as a record that
+    was not found, are logged alongside them.
+*/
+
+const EVENT_STANDARD: &str = "near_bmi";
+const EVENT_VERSION: &str = "1.0.0";
+
+// Informational logs are kept by default
+#[derive(
+  Serialize,
+  Deserialize,
+  BorshDeserialize,
+  BorshSerialize,
+  Clone,
+  Copy,
+  Debug,
+  Default,
+  PartialEq,
+  PartialOrd,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+  Quiet,
+  #[default]
+  Info,
+  Debug,
+}
+
+impl Contract {
+  // Emit an audit or lifecycle event for the signer, whatever the verbosity
+  pub(crate) fn emit_event(&mut self, event: &str, fields: Value) {
+    let account = env::signer_account_id().to_string();
+    self.emit_event_for(&account, event, fields);
+  }
+
+  // Emit an audit or lifecycle event about the data of `account`, whatever the verbosity
+  pub(crate) fn emit_event_for(&mut self, account: &AccountId, event: &str, fields: Value) {
+    self.event_seq += 1;
+    let user_seq = self.user_event_seq.entry(account.clone()).or_insert(0);
+    *user_seq += 1;
+    let line = json!({
+      "standard": EVENT_STANDARD,
+      "version": EVENT_VERSION,
+      "event": event,
+      "data": {
+        "account": account,
+        "seq": self.event_seq,
+        "user_seq": *user_seq,
+        "fields": fields,
+      },
+    });
+    env::log_str(&format!("EVENT_JSON:{}", line));
+  }
+
+  // Emit a diagnostic for the signer when the configured verbosity allows it
+  pub(crate) fn log_event(&mut self, level: Verbosity, event: &str, fields: Value) {
+    let account = env::signer_account_id().to_string();
+    self.log_event_for(&account, level, event, fields);
+  }
+
+  // Emit a diagnostic about the data of `account` when the configured verbosity allows it
+  pub(crate) fn log_event_for(
+    &mut self,
+    account: &AccountId,
+    level: Verbosity,
+    event: &str,
+    fields: Value,
+  ) {
+    if level <= self.verbosity {
+      self.emit_event_for(account, event, fields);
+    }
+  }
+}
+
+#[near_bindgen]
+impl Contract {
+  // Last sequence numbers handed out, contract-wide and for `account`
+  pub fn event_seq(&self, account: Option<AccountId>) -> (u64, u64) {
+    let user_seq = account
+      .and_then(|account| self.user_event_seq.get(&account).copied())
+      .unwrap_or(0);
+    (self.event_seq, user_seq)
+  }
+}
@@ -0,0 +1,34 @@
+use crate::*;
+
+/*  Per-method usage counters.
+    Every change method counts its calls, and calls that complete without doing what was asked
+    (no data found, user already exists, demo quota used up, ...) count as failures. Calls that
+    panic roll back their state changes, counters included, so they never show up here.
+    View calls can not write state and are not counted.
+*/
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MethodUsage {
+  pub calls: u64,
+  pub failures: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn usage_metrics(&self) -> HashMap<String, MethodUsage> {
+    self.usage.clone()
+  }
+}
+
+impl Contract {
+  pub(crate) fn track_call(&mut self, method: &str) {
+    self.usage.entry(method.to_string()).or_default().calls += 1;
+  }
+
+  pub(crate) fn track_failure(&mut self, method: &str) {
+    self.usage.entry(method.to_string()).or_default().failures += 1;
+  }
+}
@@ -0,0 +1,173 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+/*  k-anonymity guard and Laplace noise.
+    Every aggregate or research view asks this module before returning numbers about a cohort,
+    and results over fewer than `min_cohort` users are suppressed entirely. Trend snapshots are also
+    held back, per confidence filter, until the records the filter selects differ from the last
+    released snapshot by at least `min_cohort` records.
+    The guard only shapes the aggregate views and is not a privacy boundary: every stored record is
+    public anyway, through `get_data`, `export_state_page(Data)`, `project`, `compute_bsa`,
+    `compute_lbm` and the contract state itself.
+    When the owner enables noise, Laplace noise is added to aggregate counts and sums before they are
+    returned, with the budget `epsilon` split evenly across the statistics of one release. It is
+    seeded by the epoch the totals were last written in, so asking the same question twice, or about
+    a later epoch that repeats the same totals, gives the same answer and repeated queries can not be
+    averaged away. Every write epoch is a fresh release though, so a user present across n write
+    epochs is covered by n * epsilon in total.
+    This is not differential privacy and gives participants no plausible deniability. The seed comes
+    from the block's random seed, which is derivable from the block, and is kept in public contract
+    state next to the exact `population` totals, so anyone can recompute and subtract the noise. It
+    only blurs the numbers for consumers who take the view at face value.
+*/
+
+pub const DEFAULT_MIN_COHORT: u64 = 10;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct Noise {
+  epsilon: f64,
+  seed: [u8; 32],
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn set_min_cohort(&mut self, k: u64) {
+    self.track_call("set_min_cohort");
+    self.assert_owner("change the minimum cohort");
+    require!(k > 0, "Minimum cohort must be at least 1");
+    self.min_cohort = k;
+    self.emit_event("min_cohort_updated", json!({ "k": k }));
+  }
+
+  pub fn get_min_cohort(&self) -> u64 {
+    self.min_cohort
+  }
+
+  // Enable Laplace noise with budget `epsilon` per release, or disable it with null; the noise can
+  // be removed by anyone reading the state, see the module doc
+  pub fn set_noise(&mut self, epsilon: Option<f64>) {
+    self.track_call("set_noise");
+    self.assert_owner("change the noise");
+    self.noise = epsilon.map(|epsilon| {
+      require!(epsilon > 0.0, "Epsilon must be positive");
+      let seed: [u8; 32] = env::random_seed()
+        .try_into()
+        .expect("Random seed should be 32 bytes");
+      Noise { epsilon, seed }
+    });
+    self.emit_event("noise_updated", json!({ "epsilon": epsilon }));
+  }
+
+  pub fn get_noise(&self) -> Option<f64> {
+    self.noise.as_ref().map(|noise| noise.epsilon)
+  }
+}
+
+impl Contract {
+  // `value` is only released when it describes at least k users
+  pub(crate) fn k_anonymous<T>(&self, cohort: u64, value: T) -> Option<T> {
+    if cohort >= self.min_cohort {
+      Some(value)
+    } else {
+      None
+    }
+  }
+
+  // Whether the records `min_confidence` selects in `population` may be released. Walking down
+  // from the highest confidence tier, a tier holding fewer than k users, or changed by fewer than k
+  // records since `previous`, is folded into the tier below it, and a short remainder at the bottom
+  // into the groups above. A filter is released when it covers whole groups, so subtracting two
+  // filters of one snapshot, or two snapshots released for one filter, never isolates fewer than
+  // k users, while an odd tier only suppresses the filters that would cut through its group.
+  pub(crate) fn releasable(
+    &self,
+    population: &Population,
+    previous: Option<&Population>,
+    min_confidence: Option<u8>,
+  ) -> bool {
+    let before = previous.map(Population::groups).unwrap_or_default();
+    let k = self.min_cohort;
+    let passes =
+      |users: u64, changed: u64| (users == 0 || users >= k) && (changed == 0 || changed >= k);
+
+    // (highest tier, lowest tier, users, changed records) per folded group, highest first
+    let mut folded: Vec<(Option<u8>, Option<u8>, u64, u64)> = vec![];
+    let mut pending: Option<(Option<u8>, Option<u8>, u64, u64)> = None;
+    for (group, (users, changes)) in population.groups().into_iter().rev() {
+      let changed = changes - before.get(&group).map_or(0, |(_, changes)| *changes);
+      let (highest, _, held, moved) = pending.take().unwrap_or((group, group, 0, 0));
+      let merged = (highest, group, held + users, moved + changed);
+      if passes(merged.2, merged.3) {
+        folded.push(merged);
+      } else {
+        pending = Some(merged);
+      }
+    }
+    while let Some((_, lowest, users, changed)) = pending.take() {
+      let (highest, _, held, moved) = match folded.pop() {
+        Some(group) => group,
+        None => return false,
+      };
+      let merged = (highest, lowest, held + users, moved + changed);
+      if passes(merged.2, merged.3) {
+        folded.push(merged);
+      } else {
+        pending = Some(merged);
+      }
+    }
+
+    match min_confidence {
+      None => true,
+      Some(min) => folded
+        .iter()
+        .all(|(highest, lowest, _, _)| *lowest >= Some(min) || *highest < Some(min)),
+    }
+  }
+}
+
+impl Contract {
+  // Aggregate with per-epoch deterministic noise applied, when enabled
+  pub(crate) fn noisy(&self, epoch: u64, aggregate: &Aggregate) -> Aggregate {
+    let noise = match &self.noise {
+      Some(noise) => noise,
+      None => return aggregate.clone(),
+    };
+    // Users, the category histogram and every metric sum share the budget of a release.
+    // Each record adds at most a metric's `max_value` to its sum, which bounds the noise needed there.
+    let shares = 2 + registry::summed().count();
+    let sample = |field: u8, sensitivity: f64| {
+      noise.sample(epoch, field, sensitivity * shares as f64 / noise.epsilon)
+    };
+    let count = |field: u8, count: u64| (count as f64 + sample(field, 1.0)).round().max(0.0) as u64;
+    let mut noisy = Aggregate {
+      users: count(0, aggregate.users),
+      sums: BTreeMap::new(),
+      categories: [0; 4],
+      changes: aggregate.changes,
+    };
+    for (i, category) in aggregate.categories.iter().enumerate() {
+      noisy.categories[i] = count(1 + i as u8, *category);
+    }
+    for (i, (metric, max)) in registry::summed().enumerate() {
+      if let Some(sum) = aggregate.sums.get(metric.name()) {
+        let sum = (sum + sample(5 + i as u8, max)).max(0.0);
+        noisy.sums.insert(metric.name().to_string(), sum);
+      }
+    }
+    noisy
+  }
+}
+
+impl Noise {
+  // Laplace sample with the given scale, derived from (seed, epoch, field)
+  fn sample(&self, epoch: u64, field: u8, scale: f64) -> f64 {
+    let mut input = self.seed.to_vec();
+    input.extend_from_slice(&epoch.to_le_bytes());
+    input.push(field);
+    let hash = env::sha256(&input);
+    let bits = u64::from_le_bytes(hash[..8].try_into().unwrap());
+    // Uniform in (-0.5, 0.5), never touching the ends so the logarithm stays finite
+    let uniform = ((bits >> 11) as f64 + 0.5) / (1u64 << 53) as f64 - 0.5;
+    -scale * uniform.signum() * (1.0 - 2.0 * uniform.abs()).ln()
+  }
+}
@@ -0,0 +1,91 @@
+use crate::*;
+
+/*  Data processor whitelist with purpose binding.
+    Third parties may only push measurements into the contract when the owner registered them as a
+    processor and the purpose they declare on the call matches the registered one.
+    Users can exclude individual processors from ever writing their data. Exclusions are made by the
+    predecessor, so a contract the user calls can not change them on their behalf.
+*/
+
+#[near_bindgen]
+impl Contract {
+  pub fn register_processor(&mut self, processor: AccountId, purpose: String) {
+    self.track_call("register_processor");
+    self.assert_owner("register processors");
+    require!(!purpose.is_empty(), "Purpose can not be empty");
+    self.emit_event(
+      "processor_registered",
+      json!({ "processor": processor, "purpose": purpose }),
+    );
+    self.processors.insert(processor, purpose);
+  }
+
+  pub fn remove_processor(&mut self, processor: AccountId) {
+    self.track_call("remove_processor");
+    self.assert_owner("remove processors");
+    self.processors.remove(&processor);
+    self.emit_event("processor_removed", json!({ "processor": processor }));
+  }
+
+  pub fn processor_purpose(&self, processor: AccountId) -> Option<String> {
+    self.processors.get(&processor).cloned()
+  }
+
+  // Stop `processor` from writing the caller's data, whatever their grants say
+  pub fn exclude_processor(&mut self, processor: AccountId) {
+    self.track_call("exclude_processor");
+    self.assert_writable();
+    let account = env::predecessor_account_id().to_string();
+    let excluded = self.excluded_processors.entry(account.clone()).or_default();
+    if !excluded.contains(&processor) {
+      excluded.push(processor.clone());
+    }
+    self.emit_event_for(
+      &account,
+      "processor_excluded",
+      json!({ "processor": processor }),
+    );
+  }
+
+  pub fn include_processor(&mut self, processor: AccountId) {
+    self.track_call("include_processor");
+    self.assert_writable();
+    let account = env::predecessor_account_id().to_string();
+    if let Some(excluded) = self.excluded_processors.get_mut(&account) {
+      excluded.retain(|excluded| *excluded != processor);
+      if excluded.is_empty() {
+        self.excluded_processors.remove(&account);
+      }
+    }
+    self.emit_event_for(
+      &account,
+      "processor_included",
+      json!({ "processor": processor }),
+    );
+  }
+
+  pub fn excluded_processors_of(&self, account: AccountId) -> Vec<AccountId> {
+    self
+      .excluded_processors
+      .get(&account)
+      .cloned()
+      .unwrap_or_default()
+  }
+}
+
+impl Contract {
+  // The caller must be a registered processor acting for its registered purpose, not excluded by `owner`
+  pub(crate) fn assert_processor(&self, owner: &AccountId, purpose: &str) {
+    let processor = env::predecessor_account_id().to_string();
+    require!(
+      self.processors.get(&processor).map(String::as_str) == Some(purpose),
+      "Caller is not a registered processor for this purpose"
+    );
+    require!(
+      !self
+        .excluded_processors_of(owner.clone())
+        .contains(&processor),
+      "Processor is excluded by this user"
+    );
+  }
+}
@@ -0,0 +1,65 @@
+use crate::*;
+
+/*  What-if projection.
+    Starting from the user's stored weight and height, projects weight and BMI week by week for a
+    constant weekly change, using the formula their record was computed with, and lists the weeks where
+    the category changes. Frontends render this instead of duplicating the contract math.
+*/
+
+const MAX_PROJECTION_WEEKS: u32 = 520;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProjectedWeek {
+  pub week: u32,
+  pub weight: f32,
+  pub bmi: f32,
+  pub category: Category,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Projection {
+  pub series: Vec<ProjectedWeek>,
+  pub transitions: Vec<ProjectedWeek>,
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn project(
+    &self,
+    account: AccountId,
+    weekly_change_kg: f32,
+    weeks: u32,
+  ) -> Option<Projection> {
+    require!(weeks <= MAX_PROJECTION_WEEKS, "Projection is too long");
+    let data = self.data.get(&account)?;
+    let height = data.height / 100.0;
+
+    let mut series = Vec::with_capacity(weeks as usize + 1);
+    let mut transitions = vec![];
+    for week in 0..=weeks {
+      // Weight can not go below zero however long the projection runs
+      let weight = (data.weight as f32 + weekly_change_kg * week as f32).max(0.0);
+      let bmi = data.formula.bmi(weight, height);
+      let projected = ProjectedWeek {
+        week,
+        weight,
+        bmi,
+        category: Category::of(bmi),
+      };
+      if week > 0
+        && series
+          .last()
+          .is_some_and(|last: &ProjectedWeek| last.category != projected.category)
+      {
+        transitions.push(projected.clone());
+      }
+      series.push(projected);
+    }
+    Some(Projection {
+      series,
+      transitions,
+    })
+  }
+}
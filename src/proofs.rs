@@ -0,0 +1,86 @@
+use crate::canonical::{Canonical, CanonicalWriter};
+use crate::*;
+use near_sdk::json_types::{Base58CryptoHash, Base64VecU8};
+use near_sdk::CryptoHash;
+
+/*  Band attestations.
+    `prove_band` commits to "this account was in band X at time T" and keeps the commitment on chain.
+    The user picks a salt client-side and passes its hash, and hands the opening (band, time, salt)
+    to e.g. a gym, which checks it with `verify_band_proof` and learns that the contract vouched for
+    the band at that time.
+    This hides nothing from chain observers. The salt hash is a transaction argument and the account
+    and time are public, so anyone can try the four bands against the commitment and read off the
+    band. The exact BMI is public as well, through `get_data`, `export_state_page(Data)`, `project`
+    and the contract state. The proof only saves the verifier from trusting the user's word.
+    Proofs are issued for the predecessor, so a contract the user calls can not commit to their band
+    under a salt it controls, and only the newest `MAX_BAND_PROOFS` per account are kept.
+*/
+
+pub const MAX_BAND_PROOFS: usize = 8;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BandProof {
+  pub commitment: Base58CryptoHash,
+  pub issued_at: u64,
+}
+
+fn commitment(
+  account: &AccountId,
+  band: Category,
+  issued_at: u64,
+  salt_hash: Base58CryptoHash,
+) -> Base58CryptoHash {
+  let mut writer = CanonicalWriter::new();
+  writer.str(account);
+  band.write_canonical(&mut writer);
+  writer
+    .u64(issued_at)
+    .bytes(&CryptoHash::from(salt_hash))
+    .finish()
+}
+
+#[near_bindgen]
+impl Contract {
+  // Commit to the band of the caller's stored BMI as of now, under the hash of a client-side salt;
+  // the oldest proof is dropped once the caller holds `MAX_BAND_PROOFS`
+  pub fn prove_band(&mut self, salt_hash: Base58CryptoHash) -> BandProof {
+    self.track_call("prove_band");
+    let account = env::predecessor_account_id().to_string();
+    self.assert_feature(flags::BAND_PROOFS, Some(&account));
+    let band = match self.data.get(&account) {
+      Some(data) => Category::of(data.bmi),
+      None => env::panic_str("No Data Found"),
+    };
+    let issued_at = env::block_timestamp();
+    let commitment = commitment(&account, band, issued_at, salt_hash);
+    let proofs = self.band_proofs.entry(account.clone()).or_default();
+    if proofs.len() >= MAX_BAND_PROOFS {
+      proofs.remove(0);
+    }
+    proofs.push(commitment);
+    self.emit_event_for(&account, "band_proved", json!({ "commitment": commitment }));
+    BandProof {
+      commitment,
+      issued_at,
+    }
+  }
+
+  // True when the opening matches a commitment the contract issued for `account`
+  pub fn verify_band_proof(
+    &self,
+    account: AccountId,
+    band: Category,
+    issued_at: u64,
+    salt: Base64VecU8,
+  ) -> bool {
+    let salt_hash: CryptoHash = env::sha256(&Vec::from(salt))
+      .try_into()
+      .expect("sha256 should be 32 bytes");
+    let expected = commitment(&account, band, issued_at, salt_hash.into());
+    self
+      .band_proofs
+      .get(&account)
+      .is_some_and(|proofs| proofs.contains(&expected))
+  }
+}
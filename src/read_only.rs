@@ -0,0 +1,28 @@
+use crate::*;
+
+/*  Emergency read-only mode.
+    During an incident the owner can freeze every method that changes user data while leaving all
+    views, exports, reads and attestation endpoints (prove_band, verify_claim) live, so users can
+    still retrieve and prove their data. Owner configuration stays available to respond to the
+    incident, and bookkeeping such as event sequence numbers and usage counters keeps running.
+*/
+
+#[near_bindgen]
+impl Contract {
+  pub fn set_read_only(&mut self, read_only: bool) {
+    self.track_call("set_read_only");
+    self.assert_owner("change the read-only mode");
+    self.read_only = read_only;
+    self.emit_event("read_only_updated", json!({ "read_only": read_only }));
+  }
+
+  pub fn is_read_only(&self) -> bool {
+    self.read_only
+  }
+}
+
+impl Contract {
+  pub(crate) fn assert_writable(&self) {
+    require!(!self.read_only, "Contract is in read-only mode");
+  }
+}
@@ -0,0 +1,143 @@
+use crate::*;
+
+/*  Health metric registry.
+    Each metric stored in a record implements `HealthMetric`: where it lives in the record, what
+    values are valid, how it is classified and how it feeds the population aggregates. Validation,
+    the per-metric sums and means of the population trend, their privacy noise and the self-check
+    dispatch through `METRICS`, keyed by the metric's name.
+    The registry does not cover storage: a new metric still needs its own field on `Data`, a place
+    in the canonical encoding and in the storage accounting. BMI bands come from `Category::of`,
+    which the proofs, claims, projections and formula shadow call directly.
+*/
+
+// Whether a record enters or leaves the population totals
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+  Add,
+  Remove,
+}
+
+impl Change {
+  pub fn count(self, total: &mut u64) {
+    match self {
+      Change::Add => *total += 1,
+      Change::Remove => *total -= 1,
+    }
+  }
+
+  pub fn sum(self, total: &mut f64, value: f64) {
+    match self {
+      Change::Add => *total += value,
+      Change::Remove => *total -= value,
+    }
+  }
+}
+
+pub trait HealthMetric: Sync {
+  fn name(&self) -> &'static str;
+
+  // The metric's value in a stored record
+  fn read(&self, data: &Data) -> f32;
+
+  fn is_valid(&self, value: f32) -> bool {
+    value.is_finite() && value > 0.0
+  }
+
+  fn classify(&self, _value: f32) -> Option<Category> {
+    None
+  }
+
+  // Most one record may add to the metric's sum, which sizes the privacy noise; metrics without a
+  // bound are only validated and stay out of the totals
+  fn max_value(&self) -> Option<f64> {
+    None
+  }
+}
+
+pub struct Weight;
+
+impl HealthMetric for Weight {
+  fn name(&self) -> &'static str {
+    "weight"
+  }
+
+  fn read(&self, data: &Data) -> f32 {
+    data.weight as f32
+  }
+
+  fn max_value(&self) -> Option<f64> {
+    Some(300.0)
+  }
+}
+
+pub struct Height;
+
+impl HealthMetric for Height {
+  fn name(&self) -> &'static str {
+    "height"
+  }
+
+  fn read(&self, data: &Data) -> f32 {
+    data.height
+  }
+}
+
+pub struct Bmi;
+
+impl HealthMetric for Bmi {
+  fn name(&self) -> &'static str {
+    "bmi"
+  }
+
+  fn read(&self, data: &Data) -> f32 {
+    data.bmi
+  }
+
+  fn classify(&self, value: f32) -> Option<Category> {
+    Some(Category::of(value))
+  }
+
+  fn max_value(&self) -> Option<f64> {
+    Some(60.0)
+  }
+}
+
+// Inputs are validated before the value derived from them
+pub const METRICS: [&dyn HealthMetric; 3] = [&Weight, &Height, &Bmi];
+
+// Metrics summed into the population totals, with the bound on one record's value
+pub fn summed() -> impl Iterator<Item = (&'static dyn HealthMetric, f64)> {
+  METRICS
+    .into_iter()
+    .filter_map(|metric| metric.max_value().map(|max| (metric, max)))
+}
+
+// Fold one record into `aggregate` through every registered metric
+pub fn aggregate_record(aggregate: &mut Aggregate, data: &Data, change: Change) {
+  change.count(&mut aggregate.users);
+  aggregate.changes += 1;
+  for (metric, max) in summed() {
+    // Clamped to the sensitivity the privacy noise is sized for
+    let value = (metric.read(data) as f64).clamp(0.0, max);
+    change.sum(
+      aggregate.sums.entry(metric.name().to_string()).or_default(),
+      value,
+    );
+  }
+  // Classified metrics count into the category histogram
+  for metric in METRICS {
+    if let Some(category) = metric.classify(metric.read(data)) {
+      change.count(&mut aggregate.categories[category as usize]);
+    }
+  }
+}
+
+// Panics naming the first metric of `data` that is out of range
+pub fn assert_valid_record(data: &Data) {
+  for metric in METRICS {
+    require!(
+      metric.is_valid(metric.read(data)),
+      format!("Invalid {}", metric.name())
+    );
+  }
+}
@@ -0,0 +1,174 @@
+use crate::*;
+
+/*  Scoped access for third-party dApps.
+    A user authorizes another account or contract for a set of scopes, much like an OAuth grant.
+    Every scoped call checks the caller's grant first. A call that passes every check is audited
+    through the structured log, and a denied one reverts without logging anything, so the event
+    sequence numbers never repeat.
+    Grants are made and revoked by the predecessor, never the signer, so a contract the user calls
+    can not hand out access to their data on their behalf.
+*/
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Scope {
+  #[serde(rename = "read:latest")]
+  ReadLatest,
+  #[serde(rename = "write:measurements")]
+  WriteMeasurements,
+  #[serde(rename = "verify:claims")]
+  VerifyClaims,
+}
+
+// Scopes granted to one grantee, lapsing together with the consent receipt minted for them
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Grant {
+  pub scopes: Vec<Scope>,
+  pub receipt: u64,
+  pub expires_at: u64,
+}
+
+impl Grant {
+  pub fn is_expired(&self) -> bool {
+    self.expires_at <= env::block_timestamp()
+  }
+}
+
+impl Scope {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Scope::ReadLatest => "read:latest",
+      Scope::WriteMeasurements => "write:measurements",
+      Scope::VerifyClaims => "verify:claims",
+    }
+  }
+}
+
+#[near_bindgen]
+impl Contract {
+  // Replace the scopes the caller grants to `grantee`
+  pub fn authorize(&mut self, grantee: AccountId, scopes: Vec<Scope>) {
+    self.track_call("authorize");
+    self.assert_writable();
+    let owner = env::predecessor_account_id().to_string();
+    require!(owner != grantee, "Can not authorize yourself");
+    require!(!scopes.is_empty(), "At least one scope is required");
+    self.emit_event_for(
+      &owner,
+      "scopes_granted",
+      json!({ "grantee": grantee, "scopes": scopes }),
+    );
+    let consented = scopes
+      .iter()
+      .map(|scope| format!("{}@{}", scope.name(), grantee))
+      .collect();
+    let receipt = self.mint_receipt(&owner, consented);
+    let grant = Grant {
+      scopes,
+      receipt,
+      expires_at: env::block_timestamp().saturating_add(self.consent_validity_ns),
+    };
+    let replaced = self
+      .grants
+      .entry(owner.clone())
+      .or_default()
+      .insert(grantee, grant);
+    // The new receipt supersedes the one of the grant it replaces, so re-authorizing does not pile up
+    // receipts; one a stored record was written under stays as its proof
+    if let Some(replaced) = replaced {
+      if !self.receipt_in_use(&owner, replaced.receipt) {
+        self.drop_receipt(&owner, replaced.receipt);
+      }
+    }
+  }
+
+  pub fn revoke(&mut self, grantee: AccountId) {
+    self.track_call("revoke");
+    self.assert_writable();
+    let owner = env::predecessor_account_id().to_string();
+    let mut revoked = None;
+    if let Some(grants) = self.grants.get_mut(&owner) {
+      revoked = grants.remove(&grantee);
+      if grants.is_empty() {
+        self.grants.remove(&owner);
+      }
+    }
+    // Withdrawn consent must not keep showing as live
+    if let Some(revoked) = revoked {
+      self.retire_receipt(&owner, revoked.receipt);
+    }
+    self.emit_event_for(&owner, "scopes_revoked", json!({ "grantee": grantee }));
+  }
+
+  // Live scopes only; an expired grant counts as no grant even before it is swept
+  pub fn scopes_of(&self, owner: AccountId, grantee: AccountId) -> Vec<Scope> {
+    self
+      .grants
+      .get(&owner)
+      .and_then(|grants| grants.get(&grantee))
+      .filter(|grant| !grant.is_expired())
+      .map(|grant| grant.scopes.clone())
+      .unwrap_or_default()
+  }
+
+  // Read the latest stored BMI of `owner` on their behalf
+  pub fn scoped_get_data(&mut self, owner: AccountId) -> Option<String> {
+    self.track_call("scoped_get_data");
+    self.assert_scope(&owner, Scope::ReadLatest);
+    self.audit_scoped_access(&owner, Scope::ReadLatest);
+    self.read_data(owner)
+  }
+
+  // Record a device measurement for `owner`; granting write access is their consent to store it.
+  // Only registered processors may push measurements, and only for their registered purpose.
+  pub fn scoped_compute(
+    &mut self,
+    owner: AccountId,
+    weight: u32,
+    height: f32,
+    confidence: Option<u8>,
+    purpose: String,
+  ) -> i32 {
+    self.track_call("scoped_compute");
+    self.assert_writable();
+    self.assert_processor(&owner, &purpose);
+    let receipt = self.assert_scope(&owner, Scope::WriteMeasurements);
+    let record = self.measure(owner.clone(), weight, height, Source::Device, confidence);
+    self.audit_scoped_access(&owner, Scope::WriteMeasurements);
+    let recorded = self.record_measurement(record, Consent::Grant(receipt));
+    if recorded.quota_exhausted {
+      self.track_failure("scoped_compute");
+    }
+    recorded.bmi
+  }
+}
+
+impl Contract {
+  // Panics unless the caller holds `scope` from `owner`, returning the receipt of the grant;
+  // logs nothing, see `audit_scoped_access`
+  pub(crate) fn assert_scope(&self, owner: &AccountId, scope: Scope) -> u64 {
+    self.assert_feature(flags::THIRD_PARTY, Some(owner));
+    let grantee = env::predecessor_account_id().to_string();
+    let receipt = self
+      .grants
+      .get(owner)
+      .and_then(|grants| grants.get(&grantee))
+      .filter(|grant| !grant.is_expired() && grant.scopes.contains(&scope))
+      .map(|grant| grant.receipt);
+    require!(receipt.is_some(), "Missing scope for this call");
+    receipt.unwrap()
+  }
+
+  // Record a scoped call once everything it checks has passed
+  pub(crate) fn audit_scoped_access(&mut self, owner: &AccountId, scope: Scope) {
+    let grantee = env::predecessor_account_id().to_string();
+    self.emit_event_for(
+      owner,
+      "scoped_access",
+      json!({ "owner": owner, "grantee": grantee, "scope": scope }),
+    );
+  }
+}
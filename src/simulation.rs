@@ -0,0 +1,136 @@
+/*  Deterministic replay of long-running usage.
+    Thousands of synthetic users measure themselves over several years against an in-memory contract,
+    after which storage growth, the user counter and the data read back through the views are checked.
+*/
+
+use super::*;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::testing_env;
+
+const USERS: u32 = 2_000;
+const YEARS: u64 = 3;
+const NANOS_PER_DAY: u64 = 1_000_000_000 * 60 * 60 * 24;
+
+// Upper bound on the bytes a single user may add to the serialized state, excluding their account name.
+const MAX_BYTES_PER_USER: usize = 192;
+
+// Small linear congruential generator so every run replays exactly the same history.
+struct Lcg(u64);
+
+impl Lcg {
+  fn next(&mut self) -> u64 {
+    self.0 = self
+      .0
+      .wrapping_mul(6364136223846793005)
+      .wrapping_add(1442695040888963407);
+    self.0 >> 33
+  }
+
+  fn range(&mut self, low: u64, high: u64) -> u64 {
+    low + self.next() % (high - low)
+  }
+}
+
+fn account(n: u32) -> String {
+  format!("user{}.testnet", n)
+}
+
+fn act_as(account: &str, timestamp: u64) {
+  let mut builder = VMContextBuilder::new();
+  builder
+    .signer_account_id(near_sdk::AccountId::new_unchecked(account.to_string()))
+    .block_timestamp(timestamp);
+  testing_env!(builder.build());
+}
+
+fn state_size(contract: &Contract) -> usize {
+  contract
+    .try_to_vec()
+    .expect("Contract should serialize")
+    .len()
+}
+
+#[test]
+fn replay_years_of_usage() {
+  let mut rng = Lcg(412);
+  act_as("kherld.testnet", 0);
+  let mut contract = Contract::new("kherld.testnet".to_string());
+  let base_size = state_size(&contract);
+
+  // Expected outcome per user: the BMI of their first permitted measurement, if any.
+  let mut expected: Vec<Option<f32>> = Vec::with_capacity(USERS as usize);
+  let mut name_bytes = 0;
+
+  for n in 0..USERS {
+    let uid = account(n);
+    let mut timestamp = rng.range(0, 365) * NANOS_PER_DAY;
+    act_as(&uid, timestamp);
+    contract.set_user(format!("User {}", n));
+    name_bytes += uid.len() * 5 + format!("User {}", n).len();
+
+    let mut first = None;
+    while timestamp < YEARS * 365 * NANOS_PER_DAY {
+      act_as(&uid, timestamp);
+      let weight = rng.range(40, 140) as u32;
+      let height = rng.range(140, 200) as f32;
+      let permit = match rng.range(0, 3) {
+        0 => DataPermission::new(None),
+        1 => DataPermission::new(false),
+        _ => DataPermission::default(),
+      };
+      let bmi = contract.compute(weight, height, &permit);
+      assert!(bmi > 0, "BMI should be positive");
+
+      if first.is_none() && permit.0 == Some(true) {
+        let height = height / 100.0;
+        first = Some(weight as f32 / height.powi(2));
+      }
+      timestamp += rng.range(30, 120) * NANOS_PER_DAY;
+    }
+    expected.push(first);
+  }
+
+  // The user counter hands out every id exactly once.
+  assert_eq!(contract.app_user.len(), USERS as usize);
+  let mut ids: Vec<u32> = contract.app_user.values().map(|user| user.id).collect();
+  ids.sort_unstable();
+  assert!(ids.iter().copied().eq(0..USERS), "User ids should be dense");
+
+  // Stored data matches exactly what was written, and nothing else.
+  let stored = expected.iter().filter(|bmi| bmi.is_some()).count();
+  assert_eq!(contract.data.len(), stored);
+  for (n, bmi) in expected.iter().enumerate() {
+    let uid = account(n as u32);
+    let view = contract.get_data(uid.clone());
+    assert_eq!(view, bmi.map(|bmi| format!("BMI Data: {} {}", bmi, uid)));
+  }
+
+  // Paging through the export views reconstructs the stored data exactly.
+  let mut from = 0;
+  let mut exported = HashMap::new();
+  loop {
+    let page = contract.export_state_page(Collection::Data, from, 97);
+    if page.is_empty() {
+      break;
+    }
+    from += page.len() as u64;
+    for entry in page {
+      let data: Data = near_sdk::serde_json::from_value(entry["value"].clone()).unwrap();
+      exported.insert(entry["key"].as_str().unwrap().to_string(), data);
+    }
+  }
+  assert_eq!(exported.len(), contract.data.len());
+  for (uid, data) in &contract.data {
+    assert_eq!(exported[uid].canonical_hash(), data.canonical_hash());
+  }
+
+  assert!(contract.self_check().ok, "Invariants should hold");
+
+  // Storage grows linearly with users, not with the number of measurements.
+  let growth = state_size(&contract) - base_size;
+  assert!(
+    growth <= name_bytes + MAX_BYTES_PER_USER * USERS as usize,
+    "State grew by {} bytes",
+    growth
+  );
+}
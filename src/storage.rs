@@ -0,0 +1,50 @@
+use crate::*;
+
+/*  Per-user storage usage.
+    Sizes are the borsh-encoded bytes of the user's entries (key and value) in each collection,
+    which is what they occupy in the contract state, so the frontend can warn before limits are hit.
+*/
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageUsage {
+  pub app_user: u64,
+  pub data: u64,
+  pub grants: u64,
+  pub events: u64,
+  pub receipts: u64,
+  pub proofs: u64,
+  pub excluded_processors: u64,
+  pub total: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+  pub fn storage_usage_of(&self, account: AccountId) -> StorageUsage {
+    let mut usage = StorageUsage {
+      app_user: entry_size(&account, self.app_user.get(&account)),
+      data: entry_size(&account, self.data.get(&account)),
+      grants: entry_size(&account, self.grants.get(&account)),
+      events: entry_size(&account, self.user_event_seq.get(&account)),
+      receipts: entry_size(&account, self.receipts.get(&account)),
+      proofs: entry_size(&account, self.band_proofs.get(&account)),
+      excluded_processors: entry_size(&account, self.excluded_processors.get(&account)),
+      total: 0,
+    };
+    usage.total = usage.app_user
+      + usage.data
+      + usage.grants
+      + usage.events
+      + usage.receipts
+      + usage.proofs
+      + usage.excluded_processors;
+    usage
+  }
+}
+
+fn entry_size<V: BorshSerialize>(key: &AccountId, value: Option<&V>) -> u64 {
+  match value {
+    Some(value) => (key.try_to_vec().unwrap().len() + value.try_to_vec().unwrap().len()) as u64,
+    None => 0,
+  }
+}
@@ -0,0 +1,70 @@
+use crate::*;
+
+/*  Expiry sweeper.
+    Expired consents and grants already stop working when they are checked, but they stay in state
+    until swept. `sweep_expired` is permissionless and lapses them in bounded batches, in account
+    order, emitting an event for each, so expired permissions don't linger as live state. A lapsed
+    receipt is only kept while the user's stored record refers to it; all others are deleted, and so
+    are lapsed receipts whose record has since been deleted.
+*/
+
+#[near_bindgen]
+impl Contract {
+  // Lapse or delete up to `limit` expired consent receipts and grants, returning how many were swept
+  pub fn sweep_expired(&mut self, limit: u32) -> u32 {
+    self.track_call("sweep_expired");
+    self.assert_writable();
+    let now = env::block_timestamp();
+    let mut swept = 0;
+
+    let mut accounts: Vec<AccountId> = self.receipts.keys().cloned().collect();
+    accounts.sort();
+    for account in accounts {
+      let kept = self.data.get(&account).and_then(|data| data.receipt);
+      let expired: Vec<u64> = self.receipts[&account]
+        .iter()
+        .filter(|receipt| {
+          receipt.expires_at <= now && (!receipt.lapsed || Some(receipt.id) != kept)
+        })
+        .map(|receipt| receipt.id)
+        .take((limit - swept) as usize)
+        .collect();
+      for id in expired {
+        self.retire_receipt(&account, id);
+        swept += 1;
+      }
+      if swept == limit {
+        return swept;
+      }
+    }
+
+    let mut owners: Vec<AccountId> = self.grants.keys().cloned().collect();
+    owners.sort();
+    for owner in owners {
+      let mut expired: Vec<AccountId> = self.grants[&owner]
+        .iter()
+        .filter(|(_, grant)| grant.is_expired())
+        .map(|(grantee, _)| grantee.clone())
+        .collect();
+      expired.sort();
+      expired.truncate((limit - swept) as usize);
+      for grantee in expired {
+        let grants = self.grants.get_mut(&owner).unwrap();
+        grants.remove(&grantee);
+        if grants.is_empty() {
+          self.grants.remove(&owner);
+        }
+        self.emit_event_for(
+          &owner,
+          "grant_lapsed",
+          json!({ "owner": owner, "grantee": grantee }),
+        );
+        swept += 1;
+      }
+      if swept == limit {
+        return swept;
+      }
+    }
+    swept
+  }
+}
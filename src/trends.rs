@@ -0,0 +1,248 @@
+use crate::registry::Change;
+use crate::*;
+use std::collections::BTreeMap;
+
+/*  Population trend over time.
+    Running totals over every stored (opted-in) record are kept up to date on each write, and a copy is
+    written for the current trend epoch, so researchers can chart contract-wide changes without an indexer.
+    Scored records are also totalled per tier of ten confidence points, so the view can apply a minimum
+    confidence at tier boundaries. Only records computed with the live formula are counted, so BMIs
+    from different formulas never mix; changing the formula swaps which records are counted.
+    Epochs with fewer than the minimum cohort of users are suppressed by the privacy guard,
+    which also applies the owner's noise setting to the numbers that are released. So are epochs that
+    differ from the previously released one by fewer than that many records, since subtracting the
+    two would single those records out. Both are decided per confidence filter, so a small tier only
+    holds back the filters that would single it out.
+    A trend epoch is a fixed window of block time, unrelated to NEAR's validator epochs.
+*/
+
+pub const TREND_EPOCH_NS: u64 = 1_000_000_000 * 60 * 60 * 24 * 7;
+const MAX_TREND_EPOCHS: u64 = 520;
+pub const CONFIDENCE_TIER: u8 = 10;
+
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Aggregate {
+  pub users: u64,
+  // Sum per summed metric, keyed by the metric's name
+  pub sums: BTreeMap<String, f64>,
+  pub categories: [u64; 4],
+  // Records added or removed over the lifetime of the totals
+  pub changes: u64,
+}
+
+impl Aggregate {
+  fn merge(&mut self, other: &Aggregate) {
+    self.users += other.users;
+    for (metric, sum) in &other.sums {
+      *self.sums.entry(metric.clone()).or_default() += sum;
+    }
+    self.changes += other.changes;
+    for (total, count) in self.categories.iter_mut().zip(other.categories) {
+      *total += count;
+    }
+  }
+}
+
+// Totals over all records, and over records with a confidence score per confidence tier
+#[derive(
+  Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Population {
+  pub all: Aggregate,
+  pub by_confidence: BTreeMap<u8, Aggregate>,
+}
+
+impl Population {
+  pub(crate) fn record(&mut self, data: &Data, change: Change) {
+    registry::aggregate_record(&mut self.all, data, change);
+    if let Some(confidence) = data.confidence {
+      let tier = confidence - confidence % CONFIDENCE_TIER;
+      let scored = self.by_confidence.entry(tier).or_default();
+      registry::aggregate_record(scored, data, change);
+    }
+  }
+
+  // Users and changes per confidence tier, with records without a score under None
+  pub(crate) fn groups(&self) -> BTreeMap<Option<u8>, (u64, u64)> {
+    let mut groups: BTreeMap<Option<u8>, (u64, u64)> = self
+      .by_confidence
+      .iter()
+      .map(|(tier, aggregate)| (Some(*tier), (aggregate.users, aggregate.changes)))
+      .collect();
+    let (users, changes) = groups.values().fold((0, 0), |(users, changes), group| {
+      (users + group.0, changes + group.1)
+    });
+    groups.insert(None, (self.all.users - users, self.all.changes - changes));
+    groups
+  }
+
+  // Totals over the records whose confidence tier is at least `min_confidence`; records without a
+  // score only count towards the unfiltered totals
+  pub fn meeting(&self, min_confidence: Option<u8>) -> Aggregate {
+    match min_confidence {
+      None => self.all.clone(),
+      Some(min) => {
+        let mut total = Aggregate::default();
+        for aggregate in self
+          .by_confidence
+          .range(min..)
+          .map(|(_, aggregate)| aggregate)
+        {
+          total.merge(aggregate);
+        }
+        total
+      }
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpochTrend {
+  pub epoch: u64,
+  pub suppressed: bool,
+  pub users: u64,
+  // Mean per summed metric, keyed by the metric's name
+  pub means: BTreeMap<String, f32>,
+  pub proportions: Vec<(Category, f32)>,
+}
+
+impl EpochTrend {
+  fn new(epoch: u64, aggregate: &Aggregate) -> Self {
+    let per_user = |total: f64| match aggregate.users {
+      0 => 0.0,
+      users => (total / users as f64) as f32,
+    };
+    Self {
+      epoch,
+      suppressed: false,
+      users: aggregate.users,
+      means: aggregate
+        .sums
+        .iter()
+        .map(|(metric, sum)| (metric.clone(), per_user(*sum)))
+        .collect(),
+      proportions: Category::ALL
+        .iter()
+        .map(|category| {
+          (
+            *category,
+            per_user(aggregate.categories[*category as usize] as f64),
+          )
+        })
+        .collect(),
+    }
+  }
+
+  // Stand-in for an epoch whose cohort is too small to report
+  fn suppressed(epoch: u64) -> Self {
+    Self {
+      epoch,
+      suppressed: true,
+      users: 0,
+      means: BTreeMap::new(),
+      proportions: vec![],
+    }
+  }
+}
+
+pub fn current_epoch() -> u64 {
+  env::block_timestamp() / TREND_EPOCH_NS
+}
+
+#[near_bindgen]
+impl Contract {
+  // Trend per epoch in `from_epoch..=to_epoch` over records meeting `min_confidence`;
+  // epochs without writes repeat the last known totals
+  pub fn population_trend(
+    &self,
+    from_epoch: u64,
+    to_epoch: u64,
+    min_confidence: Option<u8>,
+  ) -> Vec<EpochTrend> {
+    self.assert_feature(flags::RESEARCH, None);
+    require!(
+      from_epoch <= to_epoch,
+      "from_epoch must not be after to_epoch"
+    );
+    require!(
+      to_epoch <= current_epoch(),
+      "to_epoch must not be in the future"
+    );
+    require!(
+      to_epoch - from_epoch < MAX_TREND_EPOCHS,
+      "Requested range is too long"
+    );
+    require!(
+      min_confidence.is_none_or(|min| min <= 100 && min % CONFIDENCE_TIER == 0),
+      "Minimum confidence must be a multiple of 10 up to 100"
+    );
+    // Release decisions replay the whole history, so they do not depend on the requested range
+    let mut snapshots: Vec<(&u64, &Population)> = self
+      .trend
+      .iter()
+      .filter(|(epoch, _)| **epoch <= to_epoch)
+      .collect();
+    snapshots.sort_by_key(|(epoch, _)| **epoch);
+    let mut snapshots = snapshots.into_iter().peekable();
+    let mut released: Option<&Population> = None;
+    let mut current: Option<(u64, &Population)> = None;
+    (from_epoch..=to_epoch)
+      .map(|epoch| {
+        while let Some((written, population)) = snapshots.next_if(|(written, _)| **written <= epoch)
+        {
+          current = None;
+          if self.releasable(population, released, min_confidence) {
+            released = Some(population);
+            current = Some((*written, population));
+          }
+        }
+        // Noise is drawn for the epoch the totals were written in, so repeated totals repeat it too
+        current
+          .map(|(written, population)| (written, population.meeting(min_confidence)))
+          .and_then(|(written, last)| {
+            self.k_anonymous(
+              last.users,
+              EpochTrend::new(epoch, &self.noisy(written, &last)),
+            )
+          })
+          .unwrap_or_else(|| EpochTrend::suppressed(epoch))
+      })
+      .collect()
+  }
+}
+
+impl Contract {
+  pub(crate) fn population_add(&mut self, data: &Data) {
+    self.population.record(data, Change::Add);
+    self.trend.insert(current_epoch(), self.population.clone());
+  }
+
+  pub(crate) fn population_remove(&mut self, data: &Data) {
+    if data.formula != self.formula {
+      return;
+    }
+    self.population.record(data, Change::Remove);
+    self.trend.insert(current_epoch(), self.population.clone());
+  }
+
+  // Count the records of formula `to` instead of those of the live formula
+  pub(crate) fn population_switch_formula(&mut self, to: Formula) {
+    let from = self.formula;
+    if from == to {
+      return;
+    }
+    for data in self.data.values() {
+      if data.formula == from {
+        self.population.record(data, Change::Remove);
+      } else if data.formula == to {
+        self.population.record(data, Change::Add);
+      }
+    }
+    self.trend.insert(current_epoch(), self.population.clone());
+  }
+}